@@ -1,25 +1,85 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Lines};
+use std::process;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 const MAX_ADAPTOR_JUMP: i64 = 3;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+    let mut max_jump: i64 = MAX_ADAPTOR_JUMP;
+    let mut n_examples: usize = 0;
+    let mut n_samples: usize = 0;
+    let mut seed: Option<u64> = None;
+    let mut show_extremes: bool = false;
+
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let mut count = || -> usize {
+            options.next().and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or_else(|| panic!("{} needs a number", option))
+        };
+        match option.as_str() {
+            "--examples" => n_examples = count(),
+            "--sample" => n_samples = count(),
+            "--seed" => seed = Some(count() as u64),
+            "--extremes" => show_extremes = true,
+            jump => max_jump = jump.parse::<i64>().expect("the maximum jump must be a number")
+        }
+    }
+
+    if max_jump < 1 {
+        panic!("adaptors must be able to take at least a 1 jolt jump");
+    }
+
     let aoc_reader = AocBufReader::from_file(open_file(filename));
-    let mut adaptors: Vec<i64> = read_to_vec(Box::new(aoc_reader));
+    let adaptors: Vec<i64> = read_to_vec(Box::new(aoc_reader));
 
-    let solution: i64 = find_jumps_product(&adaptors);
-    println!("The solution is {}!", solution);
+    let problems: Vec<ChainProblem> = validate_chain(&adaptors, max_jump);
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("error: {}", problem);
+        }
+        let suggestions: Vec<String> = suggest_adaptors(&problems, max_jump).iter()
+            .map(|rating| rating.to_string()).collect();
+        if !suggestions.is_empty() {
+            eprintln!("adding adaptors rated {} would complete the chain", suggestions.join(", "));
+        }
+        process::exit(1);
+    }
+
+    let analysis: ChainAnalysis = analyze_chain(&adaptors, max_jump);
+    for (jump, count) in &analysis.jumps {
+        println!("{} jumps of {}", count, jump);
+    }
+    println!("The solution is {}!", find_jumps_product(&analysis, max_jump));
+    println!("The number of valid permutations is {}!", analysis.arrangements);
 
-    let max_adaptor: i64 = *adaptors.iter().max().unwrap();
-    adaptors.push(0);
-    adaptors.sort();
-    let mut cached_calls: HashMap<i64, i64> = HashMap::new();
-    let n_permutations: i64 = find_valid_paths_to(&adaptors, max_adaptor, &mut cached_calls);
-    println!("The number of valid permutations is {}!", n_permutations);
+    let chains: AdaptorChains = AdaptorChains::new(&adaptors, max_jump);
+    for chain in chains.iter().take(n_examples) {
+        println!("example: {}", format_chain(&chain));
+    }
+    let mut rng: StdRng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy()
+    };
+    for _ in 0..n_samples {
+        if let Some(chain) = chains.sample(&mut rng) {
+            println!("sample: {}", format_chain(&chain));
+        }
+    }
+    if show_extremes {
+        if let (Some(shortest), Some(longest)) = (chains.shortest(), chains.longest()) {
+            println!("shortest ({} adaptors): {}", shortest.len() - 2, format_chain(&shortest));
+            println!("longest ({} adaptors): {}", longest.len() - 2, format_chain(&longest));
+        }
+    }
 }
 
 fn open_file(file_path: &str) -> File {
@@ -30,60 +90,259 @@ fn read_to_vec(lines: Box<dyn Iterator<Item = String>>) -> Vec<i64> {
     let mut adaptors: Vec<i64> = lines.map(
         |line| line.parse::<i64>().unwrap()
     ).collect();
-    adaptors.sort();
+    adaptors.sort_unstable();
     adaptors
 }
 
 
-fn find_valid_paths_to(adaptors: &Vec<i64>, paths_to: i64, cached_calls: &mut HashMap<i64, i64>) -> i64 {
-    // A direct approach doesn't work; We're repeating a lot
-    // of function calls, so we can cache them (there are only so many...)
-    
-    if paths_to == adaptors[0] {
-        return 1;
-    }
-
-    adaptors.iter().filter(|val| {
-        (**val < paths_to) && (paths_to - **val <= MAX_ADAPTOR_JUMP)
-    }).map(
-        |val| {
-            if cached_calls.contains_key(val) {
-                return cached_calls[val]
-            } else {
-                let result: i64 = find_valid_paths_to(
-                    adaptors,
-                    *val,
-                    cached_calls
-                );
-                cached_calls.insert(*val, result);
-                return result
+// The chain runs from the outlet (0) through the sorted adaptors to the
+// device, which is rated max_jump above the biggest adaptor.
+fn full_chain(adaptors: &[i64], max_jump: i64) -> Vec<i64> {
+    let device: i64 = adaptors.iter().max().unwrap_or(&0) + max_jump;
+    std::iter::once(0)
+        .chain(adaptors.iter().copied())
+        .chain(std::iter::once(device))
+        .collect()
+}
+
+
+#[derive(Debug, PartialEq, Eq)]
+enum ChainProblem {
+    // nothing can bridge chain[position] to chain[position + 1]
+    Gap { position: usize, from: i64, to: i64 },
+    // an adaptor can't take its input from the outlet or anything above it
+    BelowOutlet(i64)
+}
+
+
+impl fmt::Display for ChainProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainProblem::Gap { position, from, to } => write!(
+                f, "{} jolt gap between {} and {} (chain positions {} and {})",
+                to - from, from, to, position, position + 1
+            ),
+            ChainProblem::BelowOutlet(rating) => write!(f, "an adaptor is rated {}, at or below the outlet", rating)
+        }
+    }
+}
+
+
+// Every reason the full chain can't be built. Chain positions count the
+// outlet as 0, so position n is the nth smallest adaptor.
+fn validate_chain(adaptors: &[i64], max_jump: i64) -> Vec<ChainProblem> {
+    let mut problems: Vec<ChainProblem> = adaptors.iter()
+        .filter(|rating| **rating <= 0)
+        .map(|rating| ChainProblem::BelowOutlet(*rating))
+        .collect();
+    let chain: Vec<i64> = full_chain(adaptors, max_jump);
+    for (position, pair) in chain.windows(2).enumerate() {
+        if pair[1] - pair[0] > max_jump {
+            problems.push(ChainProblem::Gap { position, from: pair[0], to: pair[1] });
+        }
+    }
+    problems
+}
+
+
+// The fewest extra adaptors that close every gap: stepping up by
+// max_jump from the bottom of each gap is as far as each one can reach.
+fn suggest_adaptors(problems: &[ChainProblem], max_jump: i64) -> Vec<i64> {
+    let mut ratings: Vec<i64> = vec![];
+    for problem in problems {
+        if let ChainProblem::Gap { from, to, .. } = problem {
+            ratings.extend((1..).map(|n| from + n * max_jump).take_while(|rating| rating < to));
+        }
+    }
+    ratings
+}
+
+
+struct ChainAnalysis {
+    // how many distinct subsets of the adaptors connect outlet to device
+    arrangements: u128,
+    // how many times each joltage difference appears when every adaptor is used
+    jumps: BTreeMap<i64, u64>
+}
+
+
+fn analyze_chain(adaptors: &[i64], max_jump: i64) -> ChainAnalysis {
+    let chain: Vec<i64> = full_chain(adaptors, max_jump);
+
+    let mut jumps: BTreeMap<i64, u64> = BTreeMap::new();
+    for pair in chain.windows(2) {
+        *jumps.entry(pair[1] - pair[0]).or_insert(0) += 1;
+    }
+
+    // ways[idx] is the number of ways to get from the outlet to chain[idx].
+    // Each one is pushed forward onto the (at most max_jump) adaptors
+    // it can plug into, so this is O(n * max_jump).
+    let mut ways: Vec<u128> = vec![0; chain.len()];
+    ways[0] = 1;
+    for idx in 0..chain.len() {
+        for next in (idx + 1)..chain.len() {
+            if chain[next] - chain[idx] > max_jump {
+                break;
             }
+            ways[next] = ways[next].checked_add(ways[idx])
+                .expect("too many arrangements to count in a u128");
         }
-    ).sum()
+    }
+
+    ChainAnalysis { arrangements: ways[chain.len() - 1], jumps }
 }
 
 
+fn find_jumps_product(analysis: &ChainAnalysis, max_jump: i64) -> u64 {
+    analysis.jumps.get(&1).unwrap_or(&0) * analysis.jumps.get(&max_jump).unwrap_or(&0)
+}
+
 
+fn format_chain(chain: &[i64]) -> String {
+    chain.iter().map(|joltage| joltage.to_string()).collect::<Vec<String>>().join(" -> ")
+}
 
-fn find_jumps_product(adaptors: &Vec<i64>) -> i64 {
-    let mut one_jumps: i64 = 0;
-    let mut three_jumps: i64 = 0;
-    let mut prev_val: i64 = 0;
 
-    for val in adaptors {
-        if val - prev_val == 3 {
-            three_jumps += 1;
+// Every concrete way of connecting outlet to device. Positions refer to
+// full_chain, and ways_to_end[idx] counts the ways of getting from
+// chain[idx] to the device, which is what weights the random samples.
+struct AdaptorChains {
+    chain: Vec<i64>,
+    max_jump: i64,
+    ways_to_end: Vec<u128>
+}
+
+
+impl AdaptorChains {
+    fn new(adaptors: &[i64], max_jump: i64) -> AdaptorChains {
+        let chain: Vec<i64> = full_chain(adaptors, max_jump);
+        let mut ways_to_end: Vec<u128> = vec![0; chain.len()];
+        ways_to_end[chain.len() - 1] = 1;
+        for idx in (0..chain.len() - 1).rev() {
+            for next in (idx + 1)..chain.len() {
+                if chain[next] - chain[idx] > max_jump {
+                    break;
+                }
+                ways_to_end[idx] = ways_to_end[idx].checked_add(ways_to_end[next])
+                    .expect("too many arrangements to count in a u128");
+            }
         }
-        if val - prev_val == 1 {
-            one_jumps += 1;
+        AdaptorChains { chain, max_jump, ways_to_end }
+    }
+
+    fn device(&self) -> usize {
+        self.chain.len() - 1
+    }
+
+    fn joltages(&self, positions: &[usize]) -> Vec<i64> {
+        positions.iter().map(|idx| self.chain[*idx]).collect()
+    }
+
+    // positions we can plug into from idx that still lead to the device
+    fn next_steps(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        ((idx + 1)..self.chain.len())
+            .take_while(move |next| self.chain[*next] - self.chain[idx] <= self.max_jump)
+            .filter(move |next| self.ways_to_end[*next] > 0)
+    }
+
+    fn iter(&self) -> ChainIter<'_> {
+        ChainIter::new(self)
+    }
+
+    // Walk forward choosing each next adaptor with probability proportional
+    // to the number of chains through it, so every chain is equally likely.
+    fn sample(&self, rng: &mut impl Rng) -> Option<Vec<i64>> {
+        if self.ways_to_end[0] == 0 {
+            return None;
         }
-        prev_val = *val
+        let mut positions: Vec<usize> = vec![0];
+        let mut idx: usize = 0;
+        while idx != self.device() {
+            let mut pick: u128 = rng.gen_range(0..self.ways_to_end[idx]);
+            for next in self.next_steps(idx) {
+                if pick < self.ways_to_end[next] {
+                    idx = next;
+                    break;
+                }
+                pick -= self.ways_to_end[next];
+            }
+            positions.push(idx);
+        }
+        Some(self.joltages(&positions))
     }
 
-    // your device adaptor
-    three_jumps = three_jumps + 1;
+    fn shortest(&self) -> Option<Vec<i64>> {
+        self.extreme_chain(|candidate, best| candidate < best)
+    }
+
+    fn longest(&self) -> Option<Vec<i64>> {
+        self.extreme_chain(|candidate, best| candidate > best)
+    }
 
-    one_jumps * three_jumps
+    // length[idx] is the number of hops in the best chain from idx to the device
+    fn extreme_chain(&self, is_better: fn(usize, usize) -> bool) -> Option<Vec<i64>> {
+        if self.ways_to_end[0] == 0 {
+            return None;
+        }
+        let mut length: Vec<usize> = vec![0; self.chain.len()];
+        let mut best_next: Vec<usize> = vec![self.device(); self.chain.len()];
+        for idx in (0..self.device()).rev().filter(|idx| self.ways_to_end[*idx] > 0) {
+            let mut best: Option<usize> = None;
+            for next in self.next_steps(idx) {
+                if best.is_none() || is_better(length[next] + 1, length[idx]) {
+                    best = Some(next);
+                    length[idx] = length[next] + 1;
+                }
+            }
+            best_next[idx] = best.unwrap();
+        }
+
+        let mut positions: Vec<usize> = vec![0];
+        while *positions.last().unwrap() != self.device() {
+            positions.push(best_next[*positions.last().unwrap()]);
+        }
+        Some(self.joltages(&positions))
+    }
+}
+
+
+// Lazily walks the tree of partial chains depth first, in increasing
+// joltage order. A node's children are only added to the arena when it's
+// popped, and dead ends are never added at all, so every leaf reached is
+// a complete chain.
+struct ChainIter<'a> {
+    chains: &'a AdaptorChains,
+    tree: Tree<usize>,
+    stack: Vec<NodeID>
+}
+
+
+impl<'a> ChainIter<'a> {
+    fn new(chains: &'a AdaptorChains) -> ChainIter<'a> {
+        let tree: Tree<usize> = Tree::new(0);
+        let stack: Vec<NodeID> = if chains.ways_to_end[0] > 0 { vec![tree.root()] } else { vec![] };
+        ChainIter { chains, tree, stack }
+    }
+}
+
+
+impl<'a> Iterator for ChainIter<'a> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node_id) = self.stack.pop() {
+            let idx: usize = self.tree.get_node(&node_id).data;
+            if idx == self.chains.device() {
+                let positions: Vec<usize> = self.tree.path_to_root(&node_id).into_iter().copied().collect();
+                return Some(self.chains.joltages(&positions));
+            }
+            for next in self.chains.next_steps(idx) {
+                self.tree.add_node(&node_id, next);
+            }
+            self.stack.extend(self.tree.children(&node_id).into_iter().rev());
+        }
+        None
+    }
 }
 
 
@@ -106,7 +365,7 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
@@ -125,13 +384,22 @@ impl<T> Tree<T> {
                     parent: None,
                     first_child: None,
                     last_child: None,
+                    next_sibling: None,
                     data: root_val
                 }
             ]
         }
     }
 
-    fn get_node(&mut self, node_id: &NodeID) -> &mut Node<T> {
+    fn root(&self) -> NodeID {
+        NodeID { index: 0 }
+    }
+
+    fn get_node(&self, node_id: &NodeID) -> &Node<T> {
+        &self.nodes[node_id.index]
+    }
+
+    fn get_node_mut(&mut self, node_id: &NodeID) -> &mut Node<T> {
         &mut self.nodes[node_id.index]
     }
 
@@ -143,25 +411,43 @@ impl<T> Tree<T> {
             parent: Some(*parent),
             first_child: None,
             last_child: None,
-            data: data
+            next_sibling: None,
+            data
         };
         self.nodes.push(new_node);
 
-        let parent_node = self.get_node(parent);
-        match parent_node.last_child {
-            Some(_node_id) => {},             
+        match self.get_node(parent).last_child {
+            Some(last_child) => {
+                self.get_node_mut(&last_child).next_sibling = Some(new_node_id);
+            },
             None => {
-                parent_node.first_child = Some(new_node_id);
+                self.get_node_mut(parent).first_child = Some(new_node_id);
             }
         };
-        parent_node.last_child = Some(new_node_id);
+        self.get_node_mut(parent).last_child = Some(new_node_id);
         new_node_id
     }
 
-    fn leaves(&self) -> Vec<NodeID> {
-        self.nodes.iter().enumerate()
-            .filter(|(_idx, node)| match node.first_child {None => true, Some(_node_id) => false})
-            .map(|(idx, _node)| NodeID {index: idx}).collect()
+    fn children(&self, node_id: &NodeID) -> Vec<NodeID> {
+        let mut children: Vec<NodeID> = vec![];
+        let mut child: Option<NodeID> = self.get_node(node_id).first_child;
+        while let Some(child_id) = child {
+            children.push(child_id);
+            child = self.get_node(&child_id).next_sibling;
+        }
+        children
+    }
+
+    // the data along the path from the root down to this node
+    fn path_to_root(&self, node_id: &NodeID) -> Vec<&T> {
+        let mut path: Vec<&T> = vec![];
+        let mut node: Option<NodeID> = Some(*node_id);
+        while let Some(id) = node {
+            path.push(&self.get_node(&id).data);
+            node = self.get_node(&id).parent;
+        }
+        path.reverse();
+        path
     }
 }
 
@@ -169,10 +455,110 @@ struct Node<T> {
     parent: Option<NodeID>,
     first_child: Option<NodeID>,
     last_child: Option<NodeID>,
+    next_sibling: Option<NodeID>,
     pub data: T
 }
 
 #[derive(Clone, Copy)]
 struct NodeID {
     index: usize
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL_EXAMPLE: [i64; 11] = [1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19];
+
+    #[test]
+    fn test_analyze_chain() {
+        let analysis = analyze_chain(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP);
+        assert_eq!(analysis.arrangements, 8);
+        assert_eq!(analysis.jumps.get(&1), Some(&7));
+        assert_eq!(analysis.jumps.get(&3), Some(&5));
+        assert_eq!(find_jumps_product(&analysis, MAX_ADAPTOR_JUMP), 35);
+    }
+
+    #[test]
+    fn test_max_jump() {
+        // the device sits max_jump above the biggest adaptor
+        let analysis = analyze_chain(&SMALL_EXAMPLE, 4);
+        assert_eq!(analysis.jumps.get(&4), Some(&1));
+        assert_eq!(analyze_chain(&[1, 2, 3], 1).arrangements, 1);
+        assert_eq!(analyze_chain(&[], 3).arrangements, 1);
+    }
+
+    #[test]
+    fn test_long_chain_does_not_overflow() {
+        // one more adaptor per joltage is a tribonacci sequence, which passes
+        // i64::MAX at around 74 adaptors
+        let adaptors: Vec<i64> = (1..120).collect();
+        assert!(analyze_chain(&adaptors, 3).arrangements > i64::MAX as u128);
+    }
+
+    #[test]
+    fn test_enumerate_chains() {
+        let chains = AdaptorChains::new(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP);
+        let all: Vec<Vec<i64>> = chains.iter().collect();
+        assert_eq!(all.len(), 8);
+        assert_eq!(all[0], vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+        assert_eq!(all[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+
+        assert_eq!(AdaptorChains::new(&[1, 5], 3).iter().next(), None);
+    }
+
+    #[test]
+    fn test_sample_chains() {
+        let chains = AdaptorChains::new(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP);
+        let all: Vec<Vec<i64>> = chains.iter().collect();
+        let mut rng = StdRng::seed_from_u64(2020);
+        let mut counts: Vec<usize> = vec![0; all.len()];
+        for _ in 0..8000 {
+            let sample = chains.sample(&mut rng).unwrap();
+            counts[all.iter().position(|chain| *chain == sample).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 800 && *count < 1200), "{:?}", counts);
+
+        assert_eq!(AdaptorChains::new(&[1, 5], 3).sample(&mut rng), None);
+    }
+
+    #[test]
+    fn test_extreme_chains() {
+        let chains = AdaptorChains::new(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP);
+        assert_eq!(chains.shortest().unwrap(), vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+        assert_eq!(chains.longest().unwrap(), full_chain(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP));
+        assert_eq!(AdaptorChains::new(&[1, 5], 3).shortest(), None);
+    }
+
+    #[test]
+    fn test_validate_chain() {
+        assert_eq!(validate_chain(&SMALL_EXAMPLE, MAX_ADAPTOR_JUMP), vec![]);
+
+        let problems = validate_chain(&[-1, 1, 2, 9, 10, 21], MAX_ADAPTOR_JUMP);
+        assert_eq!(problems, vec![
+            ChainProblem::BelowOutlet(-1),
+            ChainProblem::Gap { position: 3, from: 2, to: 9 },
+            ChainProblem::Gap { position: 5, from: 10, to: 21 }
+        ]);
+        assert_eq!(problems[1].to_string(), "7 jolt gap between 2 and 9 (chain positions 3 and 4)");
+
+        // the outlet itself can be too far from the first adaptor
+        assert_eq!(validate_chain(&[5], MAX_ADAPTOR_JUMP), vec![ChainProblem::Gap { position: 0, from: 0, to: 5 }]);
+    }
+
+    #[test]
+    fn test_suggest_adaptors() {
+        let problems = validate_chain(&[1, 2, 9, 10, 21], MAX_ADAPTOR_JUMP);
+        let suggestions = suggest_adaptors(&problems, MAX_ADAPTOR_JUMP);
+        assert_eq!(suggestions, vec![5, 8, 13, 16, 19]);
+
+        let mut fixed: Vec<i64> = vec![1, 2, 9, 10, 21];
+        fixed.extend(suggestions);
+        fixed.sort_unstable();
+        assert_eq!(validate_chain(&fixed, MAX_ADAPTOR_JUMP), vec![]);
+
+        // a gap that's an exact multiple of max_jump needs one adaptor fewer
+        assert_eq!(suggest_adaptors(&validate_chain(&[1, 7], MAX_ADAPTOR_JUMP), MAX_ADAPTOR_JUMP), vec![4]);
+    }
+}
@@ -1,171 +1,577 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::convert::TryFrom;
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
 
 use itertools::Itertools;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+
+    // --part 1 and --part 2 pick the puzzle's neighbors and rules; either
+    // can then be overridden with --neighbors, --occupy and --vacate
+    let mut strategy: Box<dyn NeighborStrategy> = Box::new(Adjacent);
+    let mut rules: Rules = Rules::PART_1;
+    let mut occupy: Option<usize> = None;
+    let mut vacate: Option<usize> = None;
+    let mut show_stats: bool = false;
+    let mut animate: bool = false;
+    let mut delay: u64 = 100;
+    let mut frames_dir: Option<String> = None;
+
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--stats" => { show_stats = true; continue; },
+            "--animate" => { animate = true; continue; },
+            _ => ()
+        }
+        let value: &str = options.next().unwrap_or_else(|| panic!("{} needs a value", option));
+        let count = || value.parse::<usize>().unwrap_or_else(|_| panic!("{} needs a number", option));
+        match option.as_str() {
+            "--part" => match value {
+                "1" => { strategy = Box::new(Adjacent); rules = Rules::PART_1; },
+                "2" => { strategy = Box::new(LineOfSight { max_distance: None }); rules = Rules::PART_2; },
+                _ => panic!("there are only two parts")
+            },
+            "--neighbors" => strategy = parse_strategy(value),
+            "--occupy" => occupy = Some(count()),
+            "--vacate" => vacate = Some(count()),
+            "--delay" => delay = count() as u64,
+            "--frames" => frames_dir = Some(value.to_string()),
+            _ => panic!("unknown option {}", option)
+        }
+    }
+    rules.occupy_if_count_equals = occupy.unwrap_or(rules.occupy_if_count_equals);
+    rules.vacate_if_count_at_least = vacate.unwrap_or(rules.vacate_if_count_at_least);
+
     let aoc_reader = AocBufReader::from_file(open_file(filename));
-    let mut grid: SeatingArea = SeatingArea::from_buffer(Box::new(aoc_reader));
+    let grid: SeatingArea = SeatingArea::from_buffer(Box::new(aoc_reader));
 
-    println!(
-        "number of occupied seats at equilibrium: {}",
-        run_simulation(grid)
-    );
+    // only keep every generation around if something is going to draw them
+    let record: bool = animate || frames_dir.is_some();
+    let report: SimulationReport = run_simulation(grid, strategy.as_ref(), &rules, record);
+
+    if animate {
+        for (generation, seating_area) in report.generations.iter().enumerate() {
+            print!("\x1b[2J\x1b[H{}", render_ansi(seating_area));
+            println!("generation {}: {} occupied", generation, seating_area.n_occupied());
+            thread::sleep(Duration::from_millis(delay));
+        }
+    }
+    if let Some(dir) = frames_dir {
+        write_frames(&report.generations, Path::new(&dir)).expect("couldn't write frames");
+        println!("wrote {} frames to {}", report.generations.len(), dir);
+    }
+    if show_stats {
+        println!("{:>6} {:>8} {:>9}", "step", "changed", "occupied");
+        for (step, stats) in report.stats.iter().enumerate() {
+            println!("{:>6} {:>8} {:>9}", step + 1, stats.changed, stats.occupied);
+        }
+    }
+
+    match report.outcome {
+        Outcome::Equilibrium { steps, occupied } => {
+            println!("number of occupied seats at equilibrium: {}", occupied);
+            println!("equilibrium reached after {} steps", steps);
+        },
+        oscillation => println!("{}", oscillation)
+    }
 }
 
 fn open_file(file_path: &str) -> File {
     File::open(file_path).unwrap()
 }
 
-fn run_simulation(mut seating_area: SeatingArea) -> i64 {
-    let mut previous_seating_area: SeatingArea = SeatingArea { grid: vec![vec![]] };
-    while previous_seating_area != seating_area {
-        previous_seating_area = SeatingArea::from_other(&seating_area);
-        seating_area = seating_area.step();
+// adjacent, sight, sight:<distance> or manhattan:<radius>
+fn parse_strategy(name: &str) -> Box<dyn NeighborStrategy> {
+    let (name, size): (&str, Option<usize>) = match name.split_once(':') {
+        Some((name, size)) => (name, Some(size.parse::<usize>().expect("neighborhood size must be a number"))),
+        None => (name, None)
+    };
+    match (name, size) {
+        ("adjacent", None) => Box::new(Adjacent),
+        ("sight", max_distance) => Box::new(LineOfSight { max_distance }),
+        ("manhattan", Some(radius)) => Box::new(ManhattanRadius { radius }),
+        _ => panic!("unknown neighbor strategy {}", name)
+    }
+}
+
+// how a single step went
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct StepStats {
+    changed: usize,
+    occupied: i64
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    // nothing changes any more after this many steps
+    Equilibrium { steps: usize, occupied: i64 },
+    // generation start + period is the same as generation start, and the
+    // hall will keep cycling through the generations in between forever
+    Oscillation { start: usize, period: usize }
+}
+
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Equilibrium { steps, occupied } => {
+                write!(f, "{} seats occupied at equilibrium after {} steps", occupied, steps)
+            },
+            Outcome::Oscillation { start, period } => write!(
+                f, "seating never settles: from generation {} it repeats every {} steps", start, period
+            )
+        }
+    }
+}
+
+
+struct SimulationReport {
+    outcome: Outcome,
+    // one entry per step that changed something
+    stats: Vec<StepStats>,
+    // the starting hall and every step after it, if asked to record them
+    generations: Vec<SeatingArea>
+}
+
+
+// Steps until nothing changes or the hall comes back to a layout it has
+// been in before. Layouts are compared by fingerprint, and a repeat is
+// confirmed by replaying the simulation before calling it an oscillation.
+fn run_simulation(seating_area: SeatingArea, strategy: &dyn NeighborStrategy, rules: &Rules, record: bool) -> SimulationReport {
+    let initial: SeatingArea = seating_area.clone();
+    let mut simulation: Simulation = Simulation::new(seating_area, strategy, rules);
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    seen.insert(simulation.fingerprint(), 0);
+    let mut stats: Vec<StepStats> = vec![];
+    let mut generations: Vec<SeatingArea> = vec![];
+    if record {
+        generations.push(initial.clone());
+    }
+
+    let outcome: Outcome = loop {
+        let changed: usize = simulation.step();
+        if changed == 0 {
+            break Outcome::Equilibrium { steps: stats.len(), occupied: simulation.n_occupied() };
+        }
+        stats.push(StepStats { changed, occupied: simulation.n_occupied() });
+        if record {
+            generations.push(simulation.seating_area().clone());
+        }
+
+        let generation: usize = stats.len();
+        match seen.entry(simulation.fingerprint()) {
+            Entry::Occupied(mut entry) => {
+                let start: usize = *entry.get();
+                if same_generation(&initial, strategy, rules, start, generation) {
+                    break Outcome::Oscillation { start, period: generation - start };
+                }
+                entry.insert(generation);
+            },
+            Entry::Vacant(entry) => { entry.insert(generation); }
+        }
+    };
+
+    SimulationReport { outcome, stats, generations }
+}
+
+fn same_generation(initial: &SeatingArea, strategy: &dyn NeighborStrategy, rules: &Rules, first: usize, second: usize) -> bool {
+    let mut simulation: Simulation = Simulation::new(initial.clone(), strategy, rules);
+    for _ in 0..first {
+        simulation.step();
+    }
+    let snapshot: SeatingArea = simulation.seating_area().clone();
+    for _ in first..second {
+        simulation.step();
+    }
+    *simulation.seating_area() == snapshot
+}
+
+// floor is left blank, empty seats green and occupied ones red
+fn render_ansi(seating_area: &SeatingArea) -> String {
+    let mut frame: String = String::new();
+    for row in seating_area.seats.chunks(seating_area.n_cols().max(1)) {
+        for seat in row {
+            frame.push_str(match seat {
+                SeatState::Floor => " ",
+                SeatState::Empty => "\x1b[32mL",
+                SeatState::Occupied => "\x1b[31m#"
+            });
+        }
+        frame.push_str("\x1b[0m\n");
+    }
+    frame
+}
+
+const PIXELS_PER_SEAT: usize = 4;
+
+// a binary PPM image of the hall, PIXELS_PER_SEAT pixels across per seat
+fn write_ppm(seating_area: &SeatingArea, writer: &mut dyn Write) -> io::Result<()> {
+    let (width, height) = (seating_area.n_cols() * PIXELS_PER_SEAT, seating_area.n_rows() * PIXELS_PER_SEAT);
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+    for row in seating_area.seats.chunks(seating_area.n_cols().max(1)) {
+        let mut line: Vec<u8> = vec![];
+        for seat in row {
+            let color: [u8; 3] = match seat {
+                SeatState::Floor => [40, 40, 40],
+                SeatState::Empty => [60, 180, 75],
+                SeatState::Occupied => [220, 50, 50]
+            };
+            for _ in 0..PIXELS_PER_SEAT {
+                line.extend_from_slice(&color);
+            }
+        }
+        for _ in 0..PIXELS_PER_SEAT {
+            writer.write_all(&line)?;
+        }
     }
+    Ok(())
+}
 
-    seating_area.n_occupied()
+// frame_0000.ppm, frame_0001.ppm, ... one per generation
+fn write_frames(generations: &[SeatingArea], dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (generation, seating_area) in generations.iter().enumerate() {
+        let file = File::create(dir.join(format!("frame_{:04}.ppm", generation)))?;
+        let mut writer = BufWriter::new(file);
+        write_ppm(seating_area, &mut writer)?;
+        writer.flush()?;
+    }
+    Ok(())
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 enum SeatState {
-    floor,
-    empty,
-    occupied
+    Floor,
+    Empty,
+    Occupied
+}
+
+
+// An empty seat fills up when exactly occupy_if_count_equals of its
+// neighbors are occupied, and an occupied one empties once at least
+// vacate_if_count_at_least of them are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rules {
+    occupy_if_count_equals: usize,
+    vacate_if_count_at_least: usize
+}
+
+
+impl Rules {
+    const PART_1: Rules = Rules { occupy_if_count_equals: 0, vacate_if_count_at_least: 4 };
+    const PART_2: Rules = Rules { occupy_if_count_equals: 0, vacate_if_count_at_least: 5 };
+}
+
+
+// A position in the hall. It's signed so that stepping off the top or
+// left edge gives a negative coordinate instead of wrapping around, and
+// wide enough for any hall that fits in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Coord {
+    row: isize,
+    col: isize
+}
+
+
+impl Coord {
+    fn new(row: usize, col: usize) -> Coord {
+        Coord {
+            row: isize::try_from(row).expect("row out of range"),
+            col: isize::try_from(col).expect("column out of range")
+        }
+    }
+
+    // None only if the arithmetic itself overflows; the result may still
+    // be outside the hall
+    fn shifted(&self, drow: isize, dcol: isize) -> Option<Coord> {
+        Some(Coord { row: self.row.checked_add(drow)?, col: self.col.checked_add(dcol)? })
+    }
+}
+
+
+// Decides which seats count as a seat's neighbors. The layout of seats
+// and floor never changes, only who is sitting where. Every Coord
+// returned must be inside the hall.
+trait NeighborStrategy {
+    fn neighbors(&self, seating_area: &SeatingArea, seat: Coord) -> Vec<Coord>;
 }
 
 
+// the (up to) eight seats touching this one
+struct Adjacent;
+
+
+impl NeighborStrategy for Adjacent {
+    fn neighbors(&self, seating_area: &SeatingArea, seat: Coord) -> Vec<Coord> {
+        (-1..2).cartesian_product(-1..2)
+            .filter(|(drow, dcol)| { !(*drow == 0 && *dcol == 0) })
+            .filter_map(|(drow, dcol)| seating_area.offset(seat, drow, dcol))
+            .collect()
+    }
+}
+
+
+// the first seat in each of the eight directions, looking past floor,
+// optionally only up to max_distance away
+struct LineOfSight {
+    max_distance: Option<usize>
+}
+
+
+impl NeighborStrategy for LineOfSight {
+    fn neighbors(&self, seating_area: &SeatingArea, seat: Coord) -> Vec<Coord> {
+        (-1..2).cartesian_product(-1..2)
+            .filter(|(drow, dcol)| { !(*drow == 0 && *dcol == 0) })
+            .filter_map(|(drow, dcol)| {
+                let mut check: Coord = seat;
+                let mut distance: usize = 0;
+                while self.max_distance.is_none_or(|max_distance| distance < max_distance) {
+                    check = seating_area.offset(check, drow, dcol)?;
+                    if *seating_area.get_seat(check)? != SeatState::Floor {
+                        return Some(check);
+                    }
+                    distance += 1;
+                }
+                None
+            })
+            .collect()
+    }
+}
+
+
+// every seat within radius steps, moving only along rows and columns
+struct ManhattanRadius {
+    radius: usize
+}
+
+
+impl NeighborStrategy for ManhattanRadius {
+    fn neighbors(&self, seating_area: &SeatingArea, seat: Coord) -> Vec<Coord> {
+        // no neighbor can be further away than the far corner of the hall
+        let span: usize = seating_area.n_rows() + seating_area.n_cols();
+        let radius: isize = isize::try_from(self.radius.min(span)).unwrap();
+        (-radius..radius + 1).cartesian_product(-radius..radius + 1)
+            .filter(|(drow, dcol)| { !(*drow == 0 && *dcol == 0) })
+            .filter(|(drow, dcol)| { drow.abs() + dcol.abs() <= radius })
+            .filter_map(|(drow, dcol)| seating_area.offset(seat, drow, dcol))
+            .collect()
+    }
+}
+
+
+// The seats are stored row after row in one flat vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct SeatingArea {
-    grid: Vec<Vec<SeatState>>
+    n_cols: usize,
+    seats: Vec<SeatState>
 }
 
 
 impl SeatingArea {
     fn from_buffer(lines: Box<dyn Iterator<Item = String>>) -> SeatingArea {
-        let mut grid: Vec<Vec<SeatState>> = vec![];
-        for line in lines {
-            let mut row: Vec<SeatState> = vec![];
+        let mut n_cols: usize = 0;
+        let mut seats: Vec<SeatState> = vec![];
+        for (row, line) in lines.enumerate() {
+            if row == 0 {
+                n_cols = line.len();
+            } else if line.len() != n_cols {
+                panic!("rows of the grid must all be the same length");
+            }
             for c in line.chars() {
                 match c {
-                    '.' => row.push(SeatState::floor),
-                    'L' => row.push(SeatState::empty),
-                    '#' => row.push(SeatState::occupied),
+                    '.' => seats.push(SeatState::Floor),
+                    'L' => seats.push(SeatState::Empty),
+                    '#' => seats.push(SeatState::Occupied),
                     _ => panic!("Error reading grid!")
                 }
             }
-            grid.push(row);
         }
-        SeatingArea { grid: grid }
-    }
-
-    fn from_other(other: &SeatingArea) -> SeatingArea {
-        SeatingArea { grid: other.grid.clone() }
+        SeatingArea { n_cols, seats }
     }
 
     fn n_rows(&self) -> usize {
-        self.grid.len()
+        if self.n_cols == 0 {
+            return 0
+        }
+        self.seats.len() / self.n_cols
     }
 
     fn n_cols(&self) -> usize {
-        if self.n_rows() == 0 {
-            return 0
-        }
-        self.grid[0].len()
+        self.n_cols
     }
 
-    fn get_seat(&self, row: usize, col: usize) -> Option<&SeatState> {
+    // where coord lives in seats, or None if it's outside the hall
+    fn index(&self, coord: Coord) -> Option<usize> {
+        let row: usize = usize::try_from(coord.row).ok()?;
+        let col: usize = usize::try_from(coord.col).ok()?;
         if (row >= self.n_rows()) || (col >= self.n_cols()) {
             return None;
         }
-        Some(&self.grid[row][col])
+        Some(row * self.n_cols + col)
     }
 
-    fn get_adjacent_seats(&self, row: usize, col: usize) -> u16 {
-        ((-1 as i8)..2).cartesian_product((-1 as i8)..2)
-            .filter(|(drow, dcol)| { !(*drow == 0 && *dcol == 0) })
-            .filter(|(drow, dcol)| { drow + row as i8 >= 0 && dcol + col as i8 >= 0 })
-            .map( |(drow, dcol)| {
-                let new_row: usize = (row as i8 + drow) as usize;
-                let new_col: usize = (col as i8  + dcol) as usize;
-
-                match self.get_seat(new_row, new_col) {
-                    Some(seat) => match *seat {
-                        SeatState::occupied => 1 as u16,
-                        _ => 0 as u16
-                    },
-                    None => 0 as u16
-                }
-            }).sum()
-    }
-
-    fn _step_chair(&self, row: usize, col: usize) -> SeatState {
-        match self.get_seat(row, col) {
-            Some(seat) => match *seat {
-                SeatState::floor => return SeatState::floor,
-                SeatState::empty => {
-                    if self.get_adjacent_seats(row, col) == 0 {
-                        return SeatState::occupied
-                    } else {
-                        return SeatState::empty
-                    }
-                },
-                SeatState::occupied => {
-                    if self.get_adjacent_seats(row, col) >= 4 {
-                        return SeatState::empty
-                    } else {
-                        return SeatState::occupied
-                    }
-                }
-            },
-            None => panic!("Don't step the void!")
-        }
+    fn coords(&self) -> impl Iterator<Item = Coord> {
+        (0..self.n_rows()).cartesian_product(0..self.n_cols())
+            .map(|(row, col)| Coord::new(row, col))
+    }
+
+    fn get_seat(&self, coord: Coord) -> Option<&SeatState> {
+        self.index(coord).map(|idx| &self.seats[idx])
     }
 
-    fn step(&self) -> SeatingArea {
-        let mut new_grid: Vec<Vec<SeatState>> = vec![];
-        for row in 0..self.n_rows() {
-            let mut new_row: Vec<SeatState> = vec![];
-            for col in 0..self.n_cols() {
-                new_row.push(self._step_chair(row, col));
+    // the position (drow, dcol) away from coord, if it's in the grid
+    fn offset(&self, coord: Coord, drow: isize, dcol: isize) -> Option<Coord> {
+        coord.shifted(drow, dcol).filter(|shifted| self.index(*shifted).is_some())
+    }
+
+    fn n_occupied(&self) -> i64 {
+        self.seats.iter().filter(|seat| **seat == SeatState::Occupied).count() as i64
+    }
+}
+
+
+// Steps a seating area without reallocating. The neighbors of every seat
+// are looked up once up front, since the floor never moves, and each step
+// only re-evaluates seats that changed, or had a neighbor that changed, in
+// the step before.
+struct Simulation {
+    seating_area: SeatingArea,
+    // the back buffer; it matches seating_area between steps
+    next: Vec<SeatState>,
+    neighbors: Vec<Vec<usize>>,
+    // the seats that have each seat as a neighbor
+    dependents: Vec<Vec<usize>>,
+    rules: Rules,
+    pending: Vec<usize>,
+    queued: Vec<bool>,
+    changed: Vec<usize>,
+    n_occupied: i64,
+    // xor of seat_key over the occupied seats, kept up to date as seats
+    // change so comparing layouts doesn't mean rehashing the whole hall
+    fingerprint: u64
+}
+
+
+// a well mixed 64 bit key for each seat (splitmix64)
+fn seat_key(idx: usize) -> u64 {
+    let mut key: u64 = (idx as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    key = (key ^ (key >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    key = (key ^ (key >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    key ^ (key >> 31)
+}
+
+
+impl Simulation {
+    fn new(seating_area: SeatingArea, strategy: &dyn NeighborStrategy, rules: &Rules) -> Simulation {
+        let n_seats: usize = seating_area.seats.len();
+        let mut neighbors: Vec<Vec<usize>> = vec![vec![]; n_seats];
+        let mut dependents: Vec<Vec<usize>> = vec![vec![]; n_seats];
+        let mut pending: Vec<usize> = vec![];
+
+        for (idx, seat) in seating_area.coords().enumerate() {
+            if seating_area.seats[idx] == SeatState::Floor {
+                continue;
+            }
+            for neighbor in strategy.neighbors(&seating_area, seat) {
+                let neighbor_idx: usize = seating_area.index(neighbor).expect("neighbor outside the hall");
+                if seating_area.seats[neighbor_idx] != SeatState::Floor {
+                    neighbors[idx].push(neighbor_idx);
+                    dependents[neighbor_idx].push(idx);
+                }
             }
-            new_grid.push(new_row)
+            pending.push(idx);
+        }
+
+        let occupied: Vec<usize> = (0..n_seats)
+            .filter(|idx| seating_area.seats[*idx] == SeatState::Occupied)
+            .collect();
+
+        Simulation {
+            n_occupied: occupied.len() as i64,
+            fingerprint: occupied.into_iter().fold(0, |fingerprint, idx| fingerprint ^ seat_key(idx)),
+            next: seating_area.seats.clone(),
+            seating_area,
+            neighbors,
+            dependents,
+            rules: *rules,
+            queued: vec![false; n_seats],
+            pending,
+            changed: vec![]
         }
-        SeatingArea { grid: new_grid }
+    }
+
+    fn seating_area(&self) -> &SeatingArea {
+        &self.seating_area
     }
 
     fn n_occupied(&self) -> i64 {
-        (0..self.n_rows()).cartesian_product(0..self.n_cols())
-            .map(|(row, col)| {
-                match self.get_seat(row, col) {
-                    Some(seat) => {
-                        match *seat {
-                            SeatState::occupied => 1,
-                            _ => 0
-                        }
-                    },
-                    None => panic!("don't count the void!")
-                }
-            }).sum::<i64>()
+        self.n_occupied
+    }
+
+    fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    fn next_state(&self, idx: usize) -> SeatState {
+        let seats: &[SeatState] = &self.seating_area.seats;
+        let n_occupied = || self.neighbors[idx].iter()
+            .filter(|neighbor| seats[**neighbor] == SeatState::Occupied)
+            .count();
+        match seats[idx] {
+            SeatState::Floor => SeatState::Floor,
+            SeatState::Empty if n_occupied() == self.rules.occupy_if_count_equals => SeatState::Occupied,
+            SeatState::Occupied if n_occupied() >= self.rules.vacate_if_count_at_least => SeatState::Empty,
+            seat => seat
+        }
     }
-}
 
-impl PartialEq for SeatingArea {
-    fn eq(&self, other: &Self) -> bool {
-        if self.n_rows() != other.n_rows()
-        && self.n_cols() != other.n_cols() {
-            return false
+    // applies the rules once and returns how many seats changed
+    fn step(&mut self) -> usize {
+        self.changed.clear();
+        for &idx in self.pending.iter() {
+            let seat: SeatState = self.next_state(idx);
+            if seat != self.seating_area.seats[idx] {
+                self.next[idx] = seat;
+                self.changed.push(idx);
+            }
+        }
+        std::mem::swap(&mut self.seating_area.seats, &mut self.next);
+        for &idx in self.changed.iter() {
+            self.next[idx] = self.seating_area.seats[idx];
+            self.fingerprint ^= seat_key(idx);
+            self.n_occupied += match self.seating_area.seats[idx] {
+                SeatState::Occupied => 1,
+                _ => -1
+            };
         }
-        for row in 0..self.n_rows() {
-            for col in 0..self.n_cols() {
-                if self.get_seat(row, col).unwrap() != other.get_seat(row, col).unwrap() {
-                    return false
+
+        self.pending.clear();
+        // a seat that just changed may change straight back, depending on
+        // the rules, even if none of its neighbors did
+        for &idx in self.changed.iter() {
+            for &dependent in self.dependents[idx].iter().chain(std::iter::once(&idx)) {
+                if !self.queued[dependent] {
+                    self.queued[dependent] = true;
+                    self.pending.push(dependent);
                 }
             }
         }
-        true
+        for &idx in self.pending.iter() {
+            self.queued[idx] = false;
+        }
+
+        self.changed.len()
     }
 }
 
@@ -188,7 +594,7 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
@@ -200,28 +606,188 @@ impl Iterator for AocBufReader {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_step() {
-        let grid: Vec<Vec<SeatState>> = vec![
-            vec![SeatState::empty, SeatState::empty, SeatState::floor],
-            vec![SeatState::empty, SeatState::empty, SeatState::floor],
-            vec![SeatState::floor, SeatState::floor, SeatState::empty]
-        ];
+    fn hall(rows: &[&str]) -> SeatingArea {
+        let lines: Vec<String> = rows.iter().map(|line| line.to_string()).collect();
+        SeatingArea::from_buffer(Box::new(lines.into_iter()))
+    }
 
+    fn count_occupied_neighbors(seating_area: &SeatingArea, strategy: &dyn NeighborStrategy, row: usize, col: usize) -> usize {
+        strategy.neighbors(seating_area, Coord::new(row, col)).iter()
+            .filter(|neighbor| *seating_area.get_seat(**neighbor).unwrap() == SeatState::Occupied)
+            .count()
+    }
 
+    // re-evaluates every seat from scratch, to check Simulation against
+    fn full_step(seating_area: &SeatingArea, strategy: &dyn NeighborStrategy, rules: &Rules) -> SeatingArea {
+        let mut next: SeatingArea = seating_area.clone();
+        for (row, col) in (0..seating_area.n_rows()).cartesian_product(0..seating_area.n_cols()) {
+            let n_occupied: usize = count_occupied_neighbors(seating_area, strategy, row, col);
+            let idx: usize = seating_area.index(Coord::new(row, col)).unwrap();
+            next.seats[idx] = match seating_area.seats[idx] {
+                SeatState::Empty if n_occupied == rules.occupy_if_count_equals => SeatState::Occupied,
+                SeatState::Occupied if n_occupied >= rules.vacate_if_count_at_least => SeatState::Empty,
+                seat => seat
+            };
+        }
+        next
+    }
+
+    fn example_hall() -> SeatingArea {
+        hall(&[
+            "L.LL.LL.LL",
+            "LLLLLLL.LL",
+            "L.L.L..L..",
+            "LLLL.LL.LL",
+            "L.LL.LL.LL",
+            "L.LLLLL.LL",
+            "..L.L.....",
+            "LLLLLLLLLL",
+            "L.LLLLLL.L",
+            "L.LLLLL.LL"
+        ])
+    }
+
+    #[test]
+    fn test_step() {
         /*
             LL.    ##.    ##.
             LL. -> ##. -> #..
             ..L    ..#    ..#
         */
-        let seating_area = SeatingArea {grid: grid};
-        let full_seats = seating_area.step();
+        let seating_area = hall(&["LL.", "LL.", "..L"]);
+        assert_eq!(seating_area.n_occupied(), 0);
+        let mut simulation = Simulation::new(seating_area, &Adjacent, &Rules::PART_1);
+
+        assert_eq!(simulation.step(), 5);
+        let full_seats: &SeatingArea = simulation.seating_area();
         assert_eq!(full_seats.n_occupied(), 5);
-        assert_eq!(full_seats.get_adjacent_seats(0, 0), 3);
+        assert_eq!(count_occupied_neighbors(full_seats, &Adjacent, 0, 0), 3);
 
-        assert_eq!(seating_area.n_occupied(), 0);
-        assert_eq!(seating_area.step().n_occupied(), 5);
-        assert_eq!(seating_area.step().step().n_occupied(), 4);
-        assert_eq!(seating_area.step().step().step().n_occupied(), 4);
+        assert_eq!(simulation.step(), 1);
+        assert_eq!(simulation.seating_area().n_occupied(), 4);
+        assert_eq!(simulation.step(), 0);
+        assert_eq!(simulation.seating_area().n_occupied(), 4);
+    }
+
+    #[test]
+    fn test_n_visible() {
+        /*
+            # L .
+            L # .
+            . . #
+        */
+        let seating_area = hall(&["#L.", "L#.", "..#"]);
+        let sight = LineOfSight { max_distance: None };
+        assert_eq!(count_occupied_neighbors(&seating_area, &sight, 0, 0), 1);
+        assert_eq!(count_occupied_neighbors(&seating_area, &sight, 0, 1), 2);
+        assert_eq!(count_occupied_neighbors(&seating_area, &sight, 0, 2), 2);
+
+        // (0, 2) can't see as far as (2, 0) if it only looks one seat away
+        let short_sight = LineOfSight { max_distance: Some(1) };
+        assert_eq!(count_occupied_neighbors(&seating_area, &short_sight, 0, 2), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_manhattan_radius() {
+        let seating_area = example_hall();
+        assert_eq!(
+            ManhattanRadius { radius: 1 }.neighbors(&seating_area, Coord::new(0, 0)),
+            vec![Coord::new(0, 1), Coord::new(1, 0)]
+        );
+        assert_eq!(ManhattanRadius { radius: 2 }.neighbors(&seating_area, Coord::new(5, 5)).len(), 12);
+        assert_eq!(ManhattanRadius { radius: usize::MAX }.neighbors(&seating_area, Coord::new(5, 5)).len(), 99);
+    }
+
+    #[test]
+    fn test_both_parts() {
+        let part_1 = run_simulation(example_hall(), &Adjacent, &Rules::PART_1, false);
+        assert_eq!(part_1.outcome, Outcome::Equilibrium { steps: 5, occupied: 37 });
+        let part_2 = run_simulation(example_hall(), &LineOfSight { max_distance: None }, &Rules::PART_2, false);
+        assert_eq!(part_2.outcome, Outcome::Equilibrium { steps: 6, occupied: 26 });
+
+        let changed: Vec<usize> = part_1.stats.iter().map(|stats| stats.changed).collect();
+        let occupied: Vec<i64> = part_1.stats.iter().map(|stats| stats.occupied).collect();
+        assert_eq!(changed, vec![71, 51, 31, 21, 7]);
+        assert_eq!(occupied, vec![71, 20, 51, 30, 37]);
+        assert!(part_1.generations.is_empty());
+    }
+
+    #[test]
+    fn test_oscillation() {
+        // a seat empties as soon as it's occupied and fills as soon as it's
+        // empty, so the hall flips back and forth forever
+        let rules = Rules { occupy_if_count_equals: 0, vacate_if_count_at_least: 0 };
+        let report = run_simulation(hall(&["L.L", "...", "L.L"]), &Adjacent, &rules, true);
+        assert_eq!(report.outcome, Outcome::Oscillation { start: 0, period: 2 });
+        assert_eq!(report.generations.len(), 3);
+        assert_eq!(report.generations[0], report.generations[2]);
+        assert_eq!(report.stats, vec![StepStats { changed: 4, occupied: 4 }, StepStats { changed: 4, occupied: 0 }]);
+    }
+
+    #[test]
+    fn test_frames() {
+        let seating_area = hall(&["L#", ".L"]);
+        let mut ppm: Vec<u8> = vec![];
+        write_ppm(&seating_area, &mut ppm).unwrap();
+        let header: &[u8] = b"P6\n8 8\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 8 * 8 * 3);
+        // the top right corner is the occupied seat
+        let top_right: usize = header.len() + 7 * 3;
+        assert_eq!(&ppm[top_right..top_right + 3], &[220, 50, 50]);
+
+        assert_eq!(render_ansi(&seating_area), "\x1b[32mL\x1b[31m#\x1b[0m\n \x1b[32mL\x1b[0m\n");
+    }
+
+    #[test]
+    fn test_simulation_matches_full_steps() {
+        // stepping only the seats near a change should land on the same
+        // hall as re-evaluating every seat each round
+        let sight = LineOfSight { max_distance: None };
+        let mut simulation = Simulation::new(example_hall(), &sight, &Rules::PART_2);
+        let mut seating_area = example_hall();
+        let mut n_steps: usize = 0;
+        while simulation.step() > 0 {
+            seating_area = full_step(&seating_area, &sight, &Rules::PART_2);
+            assert_eq!(*simulation.seating_area(), seating_area);
+            n_steps += 1;
+        }
+        assert_eq!(full_step(&seating_area, &sight, &Rules::PART_2), seating_area);
+        assert_eq!(n_steps, 6);
+    }
+
+    #[test]
+    fn test_large_hall() {
+        // coordinates past 127 used to wrap around in an i8, so seats far
+        // from the top left corner saw the wrong neighbors (or none)
+        let size: usize = 300;
+        let rows: Vec<String> = (0..size)
+            .map(|row| (0..size).map(|col| if (row + col) % 7 == 0 { '.' } else { 'L' }).collect())
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(|row| row.as_str()).collect();
+        let seating_area = hall(&rows);
+
+        let around = |coord: Coord| Adjacent.neighbors(&seating_area, coord);
+        assert_eq!(around(Coord::new(200, 200)).len(), 8);
+        assert_eq!(around(Coord::new(299, 299)), vec![Coord::new(298, 298), Coord::new(298, 299), Coord::new(299, 298)]);
+        assert_eq!(around(Coord::new(0, 150)).len(), 5);
+
+        // a diagonal of floor, with a seat at each end
+        let mut diagonal: Vec<String> = (0..size).map(|_| ".".repeat(size)).collect();
+        diagonal[0].replace_range(0..1, "L");
+        diagonal[size - 1].replace_range(size - 1..size, "L");
+        let diagonal: Vec<&str> = diagonal.iter().map(|row| row.as_str()).collect();
+        let seating_area = hall(&diagonal);
+        let sight = LineOfSight { max_distance: None };
+        assert_eq!(sight.neighbors(&seating_area, Coord::new(0, 0)), vec![Coord::new(299, 299)]);
+        assert_eq!(sight.neighbors(&seating_area, Coord::new(299, 299)), vec![Coord::new(0, 0)]);
+
+        let mut simulation = Simulation::new(hall(&rows), &Adjacent, &Rules::PART_1);
+        let mut reference = hall(&rows);
+        for _ in 0..3 {
+            simulation.step();
+            reference = full_step(&reference, &Adjacent, &Rules::PART_1);
+            assert_eq!(*simulation.seating_area(), reference);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines};
+use std::ops::{Add, Mul, Neg};
+use std::process;
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -8,15 +11,112 @@ use regex::Regex;
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+
+    // --mode heading is part 1 and --mode waypoint is part 2; turns that
+    // aren't a multiple of 90 degrees need --float
+    let mut mode: Mode = Mode::Heading;
+    let mut float: bool = false;
+    let mut export: TrackExport = TrackExport { stats: false, svg: None, geojson: None };
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--mode" => {
+                let value: &str = options.next().expect("--mode needs a value");
+                mode = Mode::parse(value).unwrap_or_else(|| panic!("unknown mode {}", value));
+            },
+            "--float" => float = true,
+            "--track" => export.stats = true,
+            "--svg" => export.svg = Some(options.next().expect("--svg needs a path").to_string()),
+            "--geojson" => export.geojson = Some(options.next().expect("--geojson needs a path").to_string()),
+            _ => panic!("unknown option {}", option)
+        }
+    }
     let aoc_reader = AocBufReader::from_file(open_file(filename));
 
-    let mut turtle: Turtle = Turtle { x: 0, y: 0, orientation: Orientation::E };
     let instructions: Vec<Instruction> = parse_instruction(Box::new(aoc_reader));
-    for instruction in instructions {
-        turtle.execute(&instruction);
+    let result: Result<(), String> = if float {
+        navigate_in::<f64>(mode, &instructions)
+            .map_err(|error| error.to_string())
+            .and_then(|track| export.write(&track).map_err(|error| error.to_string()))
+    } else {
+        navigate_in::<i64>(mode, &instructions)
+            .map_err(|error| error.to_string())
+            .and_then(|track| export.write(&track).map_err(|error| error.to_string()))
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+// what to do with the track once the ship has finished sailing
+struct TrackExport {
+    stats: bool,
+    svg: Option<String>,
+    geojson: Option<String>
+}
+
+
+impl TrackExport {
+    fn write<T: Scalar>(&self, track: &Track<T>) -> io::Result<()> {
+        println!("{}", track.summary());
+        if self.stats {
+            let bounds: BoundingBox<T> = track.bounding_box();
+            println!(
+                "bounding box: x {} to {}, y {} to {}",
+                bounds.min_x, bounds.max_x, bounds.min_y, bounds.max_y
+            );
+            println!("total distance sailed: {:.3}", track.total_distance());
+            println!("furthest Manhattan distance from the start: {}", track.max_excursion());
+        }
+        if let Some(path) = &self.svg {
+            fs::write(path, track.to_svg())?;
+        }
+        if let Some(path) = &self.geojson {
+            fs::write(path, track.to_geojson())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Heading,
+    Waypoint
+}
+
+
+impl Mode {
+    fn parse(name: &str) -> Option<Mode> {
+        match name {
+            "heading" => Some(Mode::Heading),
+            "waypoint" => Some(Mode::Waypoint),
+            _ => None
+        }
     }
 
-    println!("Final position x: {}, y: {}, Manhattan dist: {}", turtle.x, turtle.y, turtle.x.abs() + turtle.y.abs());
+    fn navigator<T: Scalar>(&self) -> Box<dyn Navigator<T>> {
+        match self {
+            Mode::Heading => Box::new(HeadingNavigator::new()),
+            Mode::Waypoint => Box::new(WaypointNavigator::new())
+        }
+    }
+}
+
+
+fn navigate_in<T: Scalar>(mode: Mode, instructions: &[Instruction]) -> Result<Track<T>, NavigationError> {
+    let mut navigator: Box<dyn Navigator<T>> = mode.navigator();
+    navigate(navigator.as_mut(), instructions)
+}
+
+// runs every instruction, keeping track of where the ship was after each
+fn navigate<T: Scalar>(navigator: &mut dyn Navigator<T>, instructions: &[Instruction]) -> Result<Track<T>, NavigationError> {
+    let mut track: Track<T> = Track { points: vec![navigator.snapshot()] };
+    for instruction in instructions {
+        navigator.execute(instruction)?;
+        track.points.push(navigator.snapshot());
+    }
+    Ok(track)
 }
 
 fn open_file(file_path: &str) -> File {
@@ -32,21 +132,21 @@ fn parse_instruction(lines: Box<dyn Iterator<Item = String>>) -> Vec<Instruction
 
     let mut instructions: Vec<Instruction> = vec![];
     for line in lines {
-        let (instruction_str, val): (&str, i64) = RE.captures(&line).and_then(
-            |capture| Some((
+        let (instruction_str, val): (&str, i64) = RE.captures(&line).map(
+            |capture| (
                 capture.get(1).unwrap().as_str(),
                 capture.get(2).unwrap().as_str().parse::<i64>().unwrap()
-            ))
+            )
         ).unwrap();
         instructions.push(
             match instruction_str {
-                "N" => Instruction { translation: Some(Translation { dx: 0, dy: val }), rotation: None, forward: None },
-                "E" => Instruction { translation: Some(Translation { dx: val, dy: 0 }), rotation: None, forward: None },
-                "S" => Instruction { translation: Some(Translation { dx: 0, dy: -val }), rotation: None, forward: None },
-                "W" => Instruction { translation: Some(Translation { dx: -val, dy: 0 }), rotation: None, forward: None },
-                "R" => Instruction { translation: None, rotation: Some(Rotation::from_input("R", val)), forward: None },
-                "L" => Instruction { translation: None, rotation: Some(Rotation::from_input("L", val)), forward: None },
-                "F" => Instruction { translation: None, rotation: None, forward: Some(Forward { distance: val }) },
+                "N" => Instruction::Translate { dx: 0, dy: val },
+                "E" => Instruction::Translate { dx: val, dy: 0 },
+                "S" => Instruction::Translate { dx: 0, dy: -val },
+                "W" => Instruction::Translate { dx: -val, dy: 0 },
+                "R" => Instruction::Turn(Rotation::from_input("R", val)),
+                "L" => Instruction::Turn(Rotation::from_input("L", val)),
+                "F" => Instruction::Forward(val),
                 _ => panic!("Unimplemented instruction")
             }
         )
@@ -56,108 +156,355 @@ fn parse_instruction(lines: Box<dyn Iterator<Item = String>>) -> Vec<Instruction
 }
 
 
-struct Translation {
-    dx: i64,
-    dy: i64
+enum Instruction {
+    // N, E, S and W: move the ship in heading mode, or the waypoint in
+    // waypoint mode
+    Translate { dx: i64, dy: i64 },
+    // L and R
+    Turn(Rotation),
+    // F: along the heading, or towards the waypoint that many times
+    Forward(i64)
 }
 
 
 struct Rotation {
-    // clockwise
-    n_turns: u8
+    // clockwise, always in 0..360
+    degrees: i64
 }
 
 
 impl Rotation {
     fn from_input(l_or_r: &str, val: i64) -> Rotation {
-        let pos_or_neg: i16 = match l_or_r {
+        let pos_or_neg: i64 = match l_or_r {
             "R" => 1,
             "L" => -1,
             _ => panic!("can only create rotations from  L or R")
         };
-        let mut n_turns: i16 = pos_or_neg * (val as i16 / 90);
-        if n_turns < 0 {
-            n_turns = n_turns + 4;
+        Rotation { degrees: (pos_or_neg * val).rem_euclid(360) }
+    }
+
+    // the number of clockwise quarter turns, if that's what this is
+    fn quarter_turns(&self) -> Option<u8> {
+        if self.degrees % 90 == 0 {
+            Some((self.degrees / 90) as u8)
+        } else {
+            None
         }
-        if n_turns >= 4 {
-            n_turns = n_turns - 4;
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+enum NavigationError {
+    // integer coordinates can only turn by multiples of 90 degrees
+    InexactTurn(i64)
+}
+
+
+impl fmt::Display for NavigationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NavigationError::InexactTurn(degrees) => write!(
+                f, "can't turn {} degrees on integer coordinates (try --float)", degrees
+            )
         }
-        Rotation { n_turns: n_turns as u8 }
     }
 }
 
 
-enum Orientation {
-    N,
-    E,
-    S,
-    W
+// What the ship's coordinates are measured in. i64 keeps every position
+// exact but can only turn by right angles; f64 can turn by any angle.
+trait Scalar: 'static + Copy + Default + PartialOrd + fmt::Display + Add<Output = Self> + Mul<Output = Self> + Neg<Output = Self> {
+    fn from_distance(distance: i64) -> Self;
+
+    // (x, y) turned clockwise about the origin
+    fn rotate(x: Self, y: Self, rotation: &Rotation) -> Result<(Self, Self), NavigationError>;
+
+    fn to_f64(self) -> f64;
+
+    fn abs(self) -> Self {
+        if self < Self::default() { -self } else { self }
+    }
 }
 
 
-struct Forward {
-    distance: i64
+/* rotation by 90 CW is a matrix multiplication
+   is the linear transformation on R2:
+   | 0, 1| |x|
+   |-1, 0| |y|
+
+   (x, y) -> (y, -x)  e.g. (-1, 1) -> (1, 1)
+*/
+fn rotate_quarter_turns<T: Scalar>(mut x: T, mut y: T, n_turns: u8) -> (T, T) {
+    for _idx in 0..n_turns {
+        let tmp = x;
+        x = y;
+        y = -tmp;
+    }
+    (x, y)
 }
 
 
-struct Instruction {
-    translation: Option<Translation>,
-    rotation: Option<Rotation>,
-    forward: Option<Forward>
+impl Scalar for i64 {
+    fn from_distance(distance: i64) -> i64 {
+        distance
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn rotate(x: i64, y: i64, rotation: &Rotation) -> Result<(i64, i64), NavigationError> {
+        match rotation.quarter_turns() {
+            Some(n_turns) => Ok(rotate_quarter_turns(x, y, n_turns)),
+            None => Err(NavigationError::InexactTurn(rotation.degrees))
+        }
+    }
 }
 
 
-impl Orientation {
-    fn turn(&self) -> Orientation {
-        match self {
-            Orientation::N => Orientation::E,
-            Orientation::E => Orientation::S,
-            Orientation::S => Orientation::W,
-            Orientation::W => Orientation::N
+impl Scalar for f64 {
+    fn from_distance(distance: i64) -> f64 {
+        distance as f64
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn rotate(x: f64, y: f64, rotation: &Rotation) -> Result<(f64, f64), NavigationError> {
+        // right angles are done by swapping coordinates so they stay exact
+        if let Some(n_turns) = rotation.quarter_turns() {
+            return Ok(rotate_quarter_turns(x, y, n_turns));
         }
+        let (sin, cos) = (rotation.degrees as f64).to_radians().sin_cos();
+        Ok((x * cos + y * sin, -x * sin + y * cos))
     }
 }
 
 
-struct Turtle {
-    x: i64,
-    y: i64,
-    orientation: Orientation
+trait Navigator<T: Scalar> {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), NavigationError>;
+
+    // where the ship is
+    fn position(&self) -> (T, T);
+
+    fn snapshot(&self) -> TrackPoint<T>;
 }
 
 
-impl Turtle {
-    fn execute(&mut self, instruction: &Instruction) {
-        match &instruction.rotation {
-            Some(rotation) => {
-                for _idx in 0..rotation.n_turns {
-                    self.orientation = self.orientation.turn();
-                }
+// the ship sails in the direction it's facing (part 1)
+struct HeadingNavigator<T: Scalar> {
+    x: T,
+    y: T,
+    // one unit in the direction the ship is facing
+    heading: (T, T)
+}
+
+
+impl<T: Scalar> HeadingNavigator<T> {
+    // at the origin, facing east
+    fn new() -> HeadingNavigator<T> {
+        HeadingNavigator { x: T::default(), y: T::default(), heading: (T::from_distance(1), T::default()) }
+    }
+}
+
+
+impl<T: Scalar> Navigator<T> for HeadingNavigator<T> {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), NavigationError> {
+        match instruction {
+            Instruction::Translate { dx, dy } => {
+                self.x = self.x + T::from_distance(*dx);
+                self.y = self.y + T::from_distance(*dy);
+            },
+            Instruction::Turn(rotation) => {
+                self.heading = T::rotate(self.heading.0, self.heading.1, rotation)?;
             },
-            None => ()
+            Instruction::Forward(distance) => {
+                let distance: T = T::from_distance(*distance);
+                self.x = self.x + self.heading.0 * distance;
+                self.y = self.y + self.heading.1 * distance;
+            }
         }
-        match &instruction.translation {
-            Some(translation) => {
-                self.x = self.x + translation.dx;
-                self.y = self.y + translation.dy;
+        Ok(())
+    }
+
+    fn position(&self) -> (T, T) {
+        (self.x, self.y)
+    }
+
+    fn snapshot(&self) -> TrackPoint<T> {
+        TrackPoint { position: self.position(), heading: self.heading, waypoint: None }
+    }
+}
+
+
+// the ship sails towards a waypoint that moves with it (part 2)
+struct WaypointNavigator<T: Scalar> {
+    x: T,
+    y: T,
+    // relative to the ship
+    waypoint: (T, T)
+}
+
+
+impl<T: Scalar> WaypointNavigator<T> {
+    // at the origin, with the waypoint 10 east and 1 north
+    fn new() -> WaypointNavigator<T> {
+        WaypointNavigator { x: T::default(), y: T::default(), waypoint: (T::from_distance(10), T::from_distance(1)) }
+    }
+}
+
+
+impl<T: Scalar> Navigator<T> for WaypointNavigator<T> {
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), NavigationError> {
+        match instruction {
+            Instruction::Translate { dx, dy } => {
+                self.waypoint.0 = self.waypoint.0 + T::from_distance(*dx);
+                self.waypoint.1 = self.waypoint.1 + T::from_distance(*dy);
+            },
+            Instruction::Turn(rotation) => {
+                self.waypoint = T::rotate(self.waypoint.0, self.waypoint.1, rotation)?;
             },
-            None => ()
-        }
-        match &instruction.forward {
-            Some(forward) => {
-                match self.orientation {
-                    Orientation::N => self.y = self.y + forward.distance,
-                    Orientation::E => self.x = self.x + forward.distance,
-                    Orientation::S => self.y = self.y - forward.distance,
-                    Orientation::W => self.x = self.x - forward.distance
-                }
+            Instruction::Forward(times) => {
+                // moving to the waypoint n times is one move n times as long
+                let times: T = T::from_distance(*times);
+                self.x = self.x + self.waypoint.0 * times;
+                self.y = self.y + self.waypoint.1 * times;
             }
-            None => ()
         }
+        Ok(())
+    }
+
+    fn position(&self) -> (T, T) {
+        (self.x, self.y)
+    }
+
+    // the ship always sails towards the waypoint
+    fn snapshot(&self) -> TrackPoint<T> {
+        TrackPoint { position: self.position(), heading: self.waypoint, waypoint: Some(self.waypoint) }
     }
 }
 
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TrackPoint<T: Scalar> {
+    position: (T, T),
+    // the direction the ship would sail on F, as a vector
+    heading: (T, T),
+    // relative to the ship, in waypoint mode
+    waypoint: Option<(T, T)>
+}
+
+
+#[derive(Debug, PartialEq)]
+struct BoundingBox<T: Scalar> {
+    min_x: T,
+    min_y: T,
+    max_x: T,
+    max_y: T
+}
+
+
+// The start and every instruction after it. Positions are in the
+// puzzle's own frame: x east and y north of where the ship set off.
+struct Track<T: Scalar> {
+    points: Vec<TrackPoint<T>>
+}
+
+
+impl<T: Scalar> Track<T> {
+    fn summary(&self) -> String {
+        let (x, y) = self.points[self.points.len() - 1].position;
+        format!("final position x: {}, y: {}, Manhattan distance: {}", x, y, x.abs() + y.abs())
+    }
+
+    fn bounding_box(&self) -> BoundingBox<T> {
+        let (x, y) = self.points[0].position;
+        let mut bounds: BoundingBox<T> = BoundingBox { min_x: x, min_y: y, max_x: x, max_y: y };
+        for point in self.points.iter() {
+            let (x, y) = point.position;
+            if x < bounds.min_x { bounds.min_x = x; }
+            if y < bounds.min_y { bounds.min_y = y; }
+            if x > bounds.max_x { bounds.max_x = x; }
+            if y > bounds.max_y { bounds.max_y = y; }
+        }
+        bounds
+    }
+
+    // the length of the path sailed, in a straight line between points
+    fn total_distance(&self) -> f64 {
+        self.points.windows(2)
+            .map(|pair| {
+                let dx: f64 = pair[1].position.0.to_f64() - pair[0].position.0.to_f64();
+                let dy: f64 = pair[1].position.1.to_f64() - pair[0].position.1.to_f64();
+                dx.hypot(dy)
+            })
+            .sum()
+    }
+
+    // the furthest the ship ever got from the start
+    fn max_excursion(&self) -> T {
+        let mut furthest: T = T::default();
+        for point in self.points.iter() {
+            let distance: T = point.position.0.abs() + point.position.1.abs();
+            if distance > furthest {
+                furthest = distance;
+            }
+        }
+        furthest
+    }
+
+    // north is up, so y is flipped going into SVG's downward y axis
+    fn to_svg(&self) -> String {
+        let bounds: BoundingBox<T> = self.bounding_box();
+        let (min_x, min_y) = (bounds.min_x.to_f64(), bounds.min_y.to_f64());
+        let (width, height) = (bounds.max_x.to_f64() - min_x, bounds.max_y.to_f64() - min_y);
+        let size: f64 = width.max(height).max(1.0);
+        let margin: f64 = size / 20.0;
+        let points: Vec<String> = self.points.iter()
+            .map(|point| format!("{},{}", point.position.0.to_f64(), svg_y(point.position.1)))
+            .collect();
+        let marker = |point: &TrackPoint<T>, color: &str| format!(
+            "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"/>\n",
+            point.position.0.to_f64(), svg_y(point.position.1), size / 100.0, color
+        );
+
+        let mut svg: String = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+            min_x - margin, -(min_y + height) - margin, width + 2.0 * margin, height + 2.0 * margin
+        );
+        svg.push_str(&format!(
+            "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\"/>\n",
+            points.join(" "), size / 500.0
+        ));
+        svg.push_str(&marker(&self.points[0], "green"));
+        svg.push_str(&marker(&self.points[self.points.len() - 1], "red"));
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    // a single LineString feature; the coordinates are the puzzle's, not
+    // longitude and latitude
+    fn to_geojson(&self) -> String {
+        let coordinates: Vec<String> = self.points.iter()
+            .map(|point| format!("[{}, {}]", point.position.0, point.position.1))
+            .collect();
+        format!(
+            "{{\"type\": \"Feature\", \"properties\": {{\"total_distance\": {}, \"max_excursion\": {}}}, \
+             \"geometry\": {{\"type\": \"LineString\", \"coordinates\": [{}]}}}}\n",
+            self.total_distance(), self.max_excursion(), coordinates.join(", ")
+        )
+    }
+}
+
+
+// 0.0 - y rather than -y, so the start doesn't come out as "-0"
+fn svg_y<T: Scalar>(y: T) -> f64 {
+    0.0 - y.to_f64()
+}
+
+
 struct AocBufReader {
     iter: Lines<BufReader<File>>,
 }
@@ -177,7 +524,7 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
@@ -189,18 +536,103 @@ impl Iterator for AocBufReader {
 mod tests {
     use super::*;
 
+    fn instructions(lines: &[&str]) -> Vec<Instruction> {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        parse_instruction(Box::new(lines.into_iter()))
+    }
+
     #[test]
     fn test_rotation_parse() {
-        assert_eq!(Rotation::from_input("L", 0).n_turns, 0);
-        assert_eq!(Rotation::from_input("L", 90).n_turns, 3);
-        assert_eq!(Rotation::from_input("L", 180).n_turns, 2);
-        assert_eq!(Rotation::from_input("L", 270).n_turns, 1);
-        assert_eq!(Rotation::from_input("L", 360).n_turns, 0);
-        assert_eq!(Rotation::from_input("R", 0).n_turns, 0);
-        assert_eq!(Rotation::from_input("R", 90).n_turns, 1);
-        assert_eq!(Rotation::from_input("R", 180).n_turns, 2);
-        assert_eq!(Rotation::from_input("R", 270).n_turns, 3);
-        assert_eq!(Rotation::from_input("R", 360).n_turns, 0);
-    }
-
-}
\ No newline at end of file
+        assert_eq!(Rotation::from_input("L", 0).quarter_turns(), Some(0));
+        assert_eq!(Rotation::from_input("L", 90).quarter_turns(), Some(3));
+        assert_eq!(Rotation::from_input("L", 180).quarter_turns(), Some(2));
+        assert_eq!(Rotation::from_input("L", 270).quarter_turns(), Some(1));
+        assert_eq!(Rotation::from_input("L", 360).quarter_turns(), Some(0));
+        assert_eq!(Rotation::from_input("R", 0).quarter_turns(), Some(0));
+        assert_eq!(Rotation::from_input("R", 90).quarter_turns(), Some(1));
+        assert_eq!(Rotation::from_input("R", 180).quarter_turns(), Some(2));
+        assert_eq!(Rotation::from_input("R", 270).quarter_turns(), Some(3));
+        assert_eq!(Rotation::from_input("R", 360).quarter_turns(), Some(0));
+
+        // more than a full turn used to be read wrong, or overflow an i16
+        assert_eq!(Rotation::from_input("R", 450).quarter_turns(), Some(1));
+        assert_eq!(Rotation::from_input("L", 36000090).quarter_turns(), Some(3));
+        assert_eq!(Rotation::from_input("R", 45).quarter_turns(), None);
+        assert_eq!(Rotation::from_input("L", 45).degrees, 315);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut navigator: WaypointNavigator<i64> = WaypointNavigator { x: 0, y: 0, waypoint: (1, 1) };
+        let quarter_turn = Instruction::Turn(Rotation::from_input("R", 90));
+        navigator.execute(&quarter_turn).unwrap();
+        assert_eq!(navigator.waypoint, (1, -1));
+        navigator.execute(&quarter_turn).unwrap();
+        assert_eq!(navigator.waypoint, (-1, -1));
+        navigator.execute(&quarter_turn).unwrap();
+        assert_eq!(navigator.waypoint, (-1, 1));
+
+        let odd_turn = Instruction::Turn(Rotation::from_input("R", 30));
+        assert_eq!(navigator.execute(&odd_turn), Err(NavigationError::InexactTurn(30)));
+        assert_eq!(navigator.waypoint, (-1, 1));
+    }
+
+    #[test]
+    fn test_both_modes() {
+        let example = instructions(&["F10", "N3", "F7", "R90", "F11"]);
+        let mut heading: HeadingNavigator<i64> = HeadingNavigator::new();
+        navigate(&mut heading, &example).unwrap();
+        assert_eq!(heading.position(), (17, -8));
+        let mut waypoint: WaypointNavigator<i64> = WaypointNavigator::new();
+        navigate(&mut waypoint, &example).unwrap();
+        assert_eq!(waypoint.position(), (214, -72));
+
+        assert_eq!(
+            navigate_in::<f64>(Mode::Waypoint, &example).unwrap().summary(),
+            "final position x: 214, y: -72, Manhattan distance: 286"
+        );
+    }
+
+    #[test]
+    fn test_arbitrary_turns() {
+        let diagonal = instructions(&["L45", "F10", "R90", "F10"]);
+        assert_eq!(navigate_in::<i64>(Mode::Heading, &diagonal).err(), Some(NavigationError::InexactTurn(315)));
+        let mut navigator: HeadingNavigator<f64> = HeadingNavigator::new();
+        navigate(&mut navigator, &diagonal).unwrap();
+        assert!((navigator.x - 200f64.sqrt()).abs() < 1e-9);
+        assert!(navigator.y.abs() < 1e-9);
+
+        // turning the waypoint 45 degrees left and back again leaves it
+        // (almost exactly) where it was
+        let there_and_back = instructions(&["L45", "R45", "F2"]);
+        let mut navigator: WaypointNavigator<f64> = WaypointNavigator::new();
+        navigate(&mut navigator, &there_and_back).unwrap();
+        assert!((navigator.x - 20.0).abs() < 1e-9);
+        assert!((navigator.y - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_track() {
+        let example = instructions(&["F10", "N3", "F7", "R90", "F11"]);
+        let track: Track<i64> = navigate_in(Mode::Heading, &example).unwrap();
+        let positions: Vec<(i64, i64)> = track.points.iter().map(|point| point.position).collect();
+        assert_eq!(positions, vec![(0, 0), (10, 0), (10, 3), (17, 3), (17, 3), (17, -8)]);
+        assert_eq!(track.points[4].heading, (0, -1));
+        assert_eq!(track.points[4].waypoint, None);
+        assert_eq!(track.bounding_box(), BoundingBox { min_x: 0, min_y: -8, max_x: 17, max_y: 3 });
+        assert_eq!(track.total_distance(), 31.0);
+        assert_eq!(track.max_excursion(), 25);
+
+        let track: Track<i64> = navigate_in(Mode::Waypoint, &example).unwrap();
+        assert_eq!(track.points[1].position, (100, 10));
+        assert_eq!(track.points[2].waypoint, Some((10, 4)));
+        assert_eq!(track.points[4].heading, (4, -10));
+        assert_eq!(track.max_excursion(), 286);
+
+        let geojson: String = track.to_geojson();
+        assert!(geojson.contains("\"coordinates\": [[0, 0], [100, 10], [100, 10], [170, 38], [170, 38], [214, -72]]"));
+        let svg: String = track.to_svg();
+        assert!(svg.contains("points=\"0,0 100,-10 100,-10 170,-38 170,-38 214,72\""));
+        assert!(svg.contains("<circle cx=\"214\" cy=\"72\""));
+    }
+}
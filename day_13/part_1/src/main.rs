@@ -1,6 +1,10 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Lines};
+use std::process;
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -11,56 +15,228 @@ fn main() {
     let aoc_reader = AocBufReader::from_file(open_file(filename));
 
     let (start_time, periods) = parse_input(Box::new(aoc_reader));
-    find_departure_time(start_time, periods);
+    let schedule: Schedule = Schedule::new(periods);
+
+    // --next N lists the next N departures, --waits the wait for every
+    // bus, and --window BUS,BUS,... MINUTES [--until TIME] the first time
+    // those buses all leave within MINUTES of each other. Without --until
+    // the window search stops after DEFAULT_SEARCH_MINUTES
+    let mut options = args[2..].iter();
+    let mut window: Option<(Vec<i64>, i64)> = None;
+    let mut until: Option<i64> = None;
+    while let Some(option) = options.next() {
+        let mut value = || options.next().unwrap_or_else(|| panic!("{} needs a value", option)).as_str();
+        let number = |value: &str| value.parse::<i64>().unwrap_or_else(|_| panic!("{} needs a number", option));
+        match option.as_str() {
+            "--next" => {
+                for departure in schedule.departures(start_time).take(number(value()) as usize) {
+                    println!("{}", departure);
+                }
+            },
+            "--waits" => {
+                for wait in schedule.wait_times(start_time) {
+                    println!("bus {}: wait {}", wait.bus, wait.wait);
+                }
+            },
+            "--window" => {
+                let buses: Vec<i64> = value().split(',').map(number).collect();
+                window = Some((buses, number(value())));
+            },
+            "--until" => until = Some(number(value())),
+            _ => panic!("unknown option {}", option)
+        }
+    }
+
+    if let Some((buses, minutes)) = window {
+        match schedule.earliest_window(&buses, start_time, minutes, until) {
+            Ok(window) => {
+                println!("buses {:?} all leave within {} minutes from {}:", buses, minutes, window.start);
+                for departure in window.departures.iter() {
+                    println!("  {}", departure);
+                }
+            },
+            Err(error) => {
+                eprintln!("error: {}", error);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match schedule.next_departure(start_time) {
+        Some(wait) => println!("period: {}, wait: {}, product: {}", wait.bus, wait.wait, wait.bus * wait.wait),
+        None => {
+            eprintln!("error: there are no buses in service");
+            process::exit(1);
+        }
+    }
 }
 
 fn open_file(file_path: &str) -> File {
     File::open(file_path).unwrap()
 }
 
-fn find_departure_time(start_time: i64, periods: Vec<i64>) {
-    println!("start time: {}", start_time);
-    for p in &periods {
-        println!("{}", p);
-    }
-    /*
-        buses arrive at integer multiples
-        of the periods. For any given perirod, 
-        the next bus will arrive at
-
-        p - start % p
-    */
-    let wait_times: Vec<(i64, i64)> = periods.iter()
-        .map( |period| {
-            (*period, period - (start_time % period))
-        }).collect();
-
-    // Rust does not have a min_by_key except
-    // in it's nightly experimental release.
-    // Blech.
-    let mut min_wait_time: Option<i64> = None;
-    let mut min_period: Option<i64> = None;
-    for (period, wait_time) in wait_times {
-        if min_wait_time == None {
-            min_wait_time = Some(wait_time);
-            min_period = Some(period);
-        } else if wait_time < min_wait_time.unwrap() {
-            min_wait_time = Some(wait_time);
-            min_period = Some(period);
+// a bus leaving at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Departure {
+    bus: i64,
+    time: i64
+}
+
+
+impl fmt::Display for Departure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bus {} at {}", self.bus, self.time)
+    }
+}
+
+
+// how long until a bus leaves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Wait {
+    bus: i64,
+    wait: i64
+}
+
+
+// a set of departures, one per bus, none of them before start
+#[derive(Debug, PartialEq, Eq)]
+struct Window {
+    start: i64,
+    departures: Vec<Departure>
+}
+
+
+#[derive(Debug, PartialEq, Eq)]
+enum ScheduleError {
+    UnknownBus(i64),
+    // the buses never line up before this time
+    NoWindowBefore(i64)
+}
+
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScheduleError::UnknownBus(bus) => write!(f, "bus {} isn't on the schedule", bus),
+            ScheduleError::NoWindowBefore(time) => write!(f, "the buses don't line up before {}", time)
+        }
+    }
+}
+
+
+/*
+    buses arrive at integer multiples
+    of their periods (which are also
+    their IDs). At or after time t,
+    the next bus will arrive in
+
+    (p - t % p) % p
+*/
+struct Schedule {
+    buses: Vec<i64>
+}
+
+
+impl Schedule {
+    fn new(buses: Vec<i64>) -> Schedule {
+        Schedule { buses }
+    }
+
+    fn wait_time(bus: i64, time: i64) -> i64 {
+        (-time).rem_euclid(bus)
+    }
+
+    fn wait_times(&self, time: i64) -> Vec<Wait> {
+        self.buses.iter()
+            .map(|bus| Wait { bus: *bus, wait: Schedule::wait_time(*bus, time) })
+            .collect()
+    }
+
+    // the first bus to leave at or after time
+    fn next_departure(&self, time: i64) -> Option<Wait> {
+        self.wait_times(time).into_iter().min_by_key(|wait| wait.wait)
+    }
+
+    // every departure at or after time, in order (and by bus when two
+    // leave together), up to the last one that fits in an i64
+    fn departures(&self, time: i64) -> Departures {
+        let next: BinaryHeap<Reverse<(i64, i64)>> = self.buses.iter()
+            .filter_map(|bus| time.checked_add(Schedule::wait_time(*bus, time)).map(|time| Reverse((time, *bus))))
+            .collect();
+        Departures { next }
+    }
+
+    // How far past the start time earliest_window looks unless told
+    // otherwise. It walks the departures one by one, and with a handful of
+    // real buses the LCM runs to 1e12 minutes or more.
+    const DEFAULT_SEARCH_MINUTES: i64 = 10_000_000;
+
+    // how often the whole schedule repeats, if that fits in an i64
+    fn lcm(&self) -> Option<i64> {
+        self.buses.iter().try_fold(1, |lcm: i64, bus| (lcm / gcd(lcm, *bus)).checked_mul(*bus))
+    }
+
+    // The earliest departures of these buses, one each and none before
+    // time, that all fit within window minutes. The search gives up once
+    // the first departure would be at or after until. By default that's
+    // one LCM of the periods after time, since the buses all leave together
+    // at some multiple of the LCM before then, but capped at
+    // DEFAULT_SEARCH_MINUTES so a long LCM can't keep it going for ever.
+    fn earliest_window(&self, buses: &[i64], time: i64, window: i64, until: Option<i64>) -> Result<Window, ScheduleError> {
+        if let Some(bus) = buses.iter().find(|bus| !self.buses.contains(bus)) {
+            return Err(ScheduleError::UnknownBus(*bus));
         }
+        let group: Schedule = Schedule::new(buses.to_vec());
+        let until: i64 = until.unwrap_or_else(|| {
+            let minutes: i64 = group.lcm().map_or(Schedule::DEFAULT_SEARCH_MINUTES, |lcm| lcm.min(Schedule::DEFAULT_SEARCH_MINUTES));
+            time.saturating_add(minutes)
+        });
+
+        // the first departure of a window is always one of the group's
+        group.departures(time)
+            .take_while(|departure| departure.time < until)
+            .filter_map(|first| {
+                let departures: Option<Vec<Departure>> = buses.iter()
+                    .map(|bus| {
+                        let time: i64 = first.time.checked_add(Schedule::wait_time(*bus, first.time))?;
+                        Some(Departure { bus: *bus, time })
+                    })
+                    .collect();
+                Some(Window { start: first.time, departures: departures? })
+            })
+            .find(|candidate| candidate.departures.iter().all(|departure| departure.time - candidate.start <= window))
+            .ok_or(ScheduleError::NoWindowBefore(until))
     }
+}
+
 
-    println!(
-        "period: {}, wait: {}, product: {}",
-        min_period.unwrap(),
-        min_wait_time.unwrap(),
-        min_period.unwrap() * min_wait_time.unwrap()
-    )
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+
+struct Departures {
+    next: BinaryHeap<Reverse<(i64, i64)>>
+}
+
+
+impl Iterator for Departures {
+    type Item = Departure;
+
+    fn next(&mut self) -> Option<Departure> {
+        let Reverse((time, bus)) = self.next.pop()?;
+        // a bus whose next departure doesn't fit in an i64 stops running
+        if let Some(next_time) = time.checked_add(bus) {
+            self.next.push(Reverse((next_time, bus)));
+        }
+        Some(Departure { bus, time })
+    }
 }
 
 fn parse_input(mut lines: Box<dyn Iterator<Item = String>>) -> (i64, Vec<i64>) {
-    let start_time: i64 = lines.next().and_then(|line| {
-            Some(line.as_str().parse::<i64>().unwrap())
+    let start_time: i64 = lines.next().map(|line| {
+            line.as_str().parse::<i64>().unwrap()
         }
     ).unwrap();
 
@@ -96,7 +272,7 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
@@ -108,6 +284,66 @@ impl Iterator for AocBufReader {
 mod tests {
     use super::*;
 
+    fn example_schedule() -> (i64, Schedule) {
+        let lines: Vec<String> = vec!["939".to_string(), "7,13,x,x,59,x,31,19".to_string()];
+        let (start_time, periods) = parse_input(Box::new(lines.into_iter()));
+        (start_time, Schedule::new(periods))
+    }
+
+    #[test]
+    fn test_next_departure() {
+        let (start_time, schedule) = example_schedule();
+        assert_eq!(schedule.next_departure(start_time), Some(Wait { bus: 59, wait: 5 }));
+        // a bus leaving right now doesn't mean waiting a whole period
+        assert_eq!(schedule.next_departure(944), Some(Wait { bus: 59, wait: 0 }));
+        assert_eq!(Schedule::new(vec![]).next_departure(start_time), None);
+
+        let waits: Vec<i64> = schedule.wait_times(start_time).iter().map(|wait| wait.wait).collect();
+        assert_eq!(waits, vec![6, 10, 5, 22, 11]);
+
+        let departures: Vec<Departure> = schedule.departures(start_time).take(6).collect();
+        assert_eq!(departures, vec![
+            Departure { bus: 59, time: 944 },
+            Departure { bus: 7, time: 945 },
+            Departure { bus: 13, time: 949 },
+            Departure { bus: 19, time: 950 },
+            Departure { bus: 7, time: 952 },
+            Departure { bus: 7, time: 959 }
+        ]);
+    }
 
+    #[test]
+    fn test_earliest_window() {
+        let (start_time, schedule) = example_schedule();
+        let window: Window = schedule.earliest_window(&[7, 13], start_time, 1, None).unwrap();
+        assert_eq!(window, Window {
+            start: 987,
+            departures: vec![Departure { bus: 7, time: 987 }, Departure { bus: 13, time: 988 }]
+        });
 
+        assert_eq!(schedule.earliest_window(&[7, 13], start_time, 1, Some(987)), Err(ScheduleError::NoWindowBefore(987)));
+        assert_eq!(schedule.earliest_window(&[7, 11], start_time, 1, None), Err(ScheduleError::UnknownBus(11)));
+
+        // by default the search covers one LCM of the periods, after which
+        // the buses can only repeat themselves, or DEFAULT_SEARCH_MINUTES
+        // if that's sooner
+        assert_eq!(schedule.lcm(), Some(7 * 13 * 59 * 31 * 19));
+        assert_eq!(schedule.earliest_window(&[7, 13], start_time, -1, None), Err(ScheduleError::NoWindowBefore(939 + 91)));
+        assert_eq!(schedule.earliest_window(&[59, 31], start_time, 0, None).unwrap().start, 1829);
+        let long: Schedule = Schedule::new(vec![9973, 9967]);
+        assert_eq!(
+            long.earliest_window(&[9973, 9967], 1, 0, None),
+            Err(ScheduleError::NoWindowBefore(1 + Schedule::DEFAULT_SEARCH_MINUTES))
+        );
+        assert_eq!(long.earliest_window(&[9973, 9967], 1, 0, Some(9973 * 9967 + 1)).unwrap().start, 9973 * 9967);
+    }
+
+    #[test]
+    fn test_departures_near_overflow() {
+        let departures: Vec<Departure> = Schedule::new(vec![7]).departures(i64::MAX - 10).collect();
+        let times: Vec<i64> = departures.iter().map(|departure| departure.time).collect();
+        assert_eq!(times, vec![i64::MAX - 7, i64::MAX]);
+        assert_eq!(Schedule::new(vec![7, 13]).lcm(), Some(91));
+        assert_eq!(Schedule::new(vec![i64::MAX, 2]).lcm(), None);
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Lines};
+use std::process;
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -8,44 +9,106 @@ use regex::Regex;
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+    let explain: bool = args[2..].iter().any(|arg| arg == "--explain");
     let aoc_reader = AocBufReader::from_file(open_file(filename));
 
     let inputs: Vec<(i64, i64)> = parse_input(Box::new(aoc_reader));
-    find_departure_time(inputs);
+    if explain {
+        print!("{}", explain_departure_time(&inputs));
+    }
+    match find_departure_time(&inputs) {
+        Ok(time) => println!("earliest time the buses depart in order: {}", time),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(1);
+        }
+    }
 }
 
 fn open_file(file_path: &str) -> File {
     File::open(file_path).unwrap()
 }
 
-fn find_departure_time(periods_remainders: Vec<(i64, i64)>) {
-    let mut pr_iter = periods_remainders.iter();
-    let (mut running_product, _): (i64, i64) = *pr_iter.next().unwrap();
-    let mut running_sum: i64 = 0;
-
-    // There some funky stuff happening here. We iteratively find
-    // a time that works for each remainder. _A_ time t_i which
-    // equals r_i mod p_i for each remainder. When we move to the
-    // next bus, we want to find a time t_i+1 that satisfies all
-    // previous buses as well. We need to take the previous time
-    // and add the least common multiple (LCM) of the previous buses
-    // periods, because this will be the smallest number that is
-    // 0 mod all the previous ps. Our buses all have priume periods,
-    // so the LCM of any collection of buses is their product.
-    for (period, remainder) in pr_iter {
-        while running_sum % period != *remainder {
-            running_sum += running_product;
+// The time we're after is a solution to one congruence per bus, so this
+// is the Chinese remainder theorem. An error if the buses can never line
+// up, or only line up further out than an i128 can count.
+fn find_departure_time(periods_offsets: &[(i64, i64)]) -> Result<i128, crt::CrtError> {
+    crt::solve(&congruences(periods_offsets)).map(|solution| solution.residue)
+}
+
+/*
+    The lore says the offset of a bus is how many
+    minutes after our special time t_0 it will need
+    to arrive. That means that t_0 occurs offset
+    units _before_ a time which is 0 mod p (the
+    period of the bus), i.e.
+
+    t_0 = -offset (mod p)
+*/
+fn congruences(periods_offsets: &[(i64, i64)]) -> Vec<crt::Congruence> {
+    periods_offsets.iter()
+        .map(|(period, offset)| crt::Congruence::new(-*offset as i128, *period as i128))
+        .collect()
+}
+
+// The working behind find_departure_time: the congruence for each bus,
+// each step of combining them, and a check of the answer against the
+// original offsets.
+fn explain_departure_time(periods_offsets: &[(i64, i64)]) -> String {
+    let mut explanation: String = String::from("congruences:\n");
+    for ((period, offset), congruence) in periods_offsets.iter().zip(congruences(periods_offsets)) {
+        explanation.push_str(&format!(
+            "  bus {} leaves at t + {}: t \u{2261} {} (mod {})\n", period, offset, congruence.residue, congruence.modulus
+        ));
+    }
+
+    let (solution, steps) = crt::solve_with_steps(&congruences(periods_offsets));
+    explanation.push_str("combining:\n");
+    for step in steps.iter() {
+        let (first, second) = (&step.first, &step.second);
+        explanation.push_str(&format!(
+            "  t \u{2261} {} (mod {}) and t \u{2261} {} (mod {})\n",
+            first.residue, first.modulus, second.residue, second.modulus
+        ));
+        explanation.push_str(&format!(
+            "    gcd({}, {}) = {} = {} * {} + {} * {}\n",
+            first.modulus, second.modulus, step.gcd, first.modulus, step.bezout.0, second.modulus, step.bezout.1
+        ));
+        match &step.combined {
+            Ok(combined) => explanation.push_str(&format!(
+                "    => t \u{2261} {} (mod {})\n", combined.residue, combined.modulus
+            )),
+            Err(crt::CrtError::NoSolution) => explanation.push_str(&format!(
+                "    => no solution: {} - {} isn't a multiple of {}\n", second.residue, first.residue, step.gcd
+            )),
+            Err(crt::CrtError::Overflow) => explanation.push_str(&format!(
+                "    => overflow: the combined modulus {} * {} / {} doesn't fit in an i128\n",
+                first.modulus, second.modulus, step.gcd
+            ))
         }
-        running_product *= period;
-        println!("{}", running_product);
     }
 
-    println!("{}", running_sum);   
+    if let Ok(solution) = solution {
+        explanation.push_str(&format!("verification of t = {}:\n", solution.residue));
+        for (period, offset) in periods_offsets.iter() {
+            let departure: i128 = solution.residue + *offset as i128;
+            let (quotient, remainder) = (departure / *period as i128, departure % *period as i128);
+            explanation.push_str(&format!(
+                "  t + {} = {} = {} * {}{}\n",
+                offset,
+                departure,
+                period,
+                quotient,
+                if remainder == 0 { String::from(" ok") } else { format!(" + {} WRONG", remainder) }
+            ));
+        }
+    }
+    explanation
 }
 
 fn parse_input(mut lines: Box<dyn Iterator<Item = String>>) -> Vec<(i64, i64)> {
-    let _: i64 = lines.next().and_then(|line| {
-            Some(line.as_str().parse::<i64>().unwrap())
+    let _: i64 = lines.next().map(|line| {
+            line.as_str().parse::<i64>().unwrap()
         }
     ).unwrap();
 
@@ -59,23 +122,163 @@ fn parse_input(mut lines: Box<dyn Iterator<Item = String>>) -> Vec<(i64, i64)> {
         .filter(|(_idx, capture)| {
             capture.as_str() != "x"
         })
+        // the index in this list is the bus's offset
         .map( |(idx, capture)| {
-            let period = capture.as_str().parse::<i64>().unwrap();
-            // The lore says the index in this list is how many
-            // minutes after our special time t_0, the bus with
-            // this period will need to arrive. That means that
-            // the special time t_0 occurs idx  units _before_
-            // a time which is 0 mod p (the period of the bus)
-            let mut idx = period - idx as i64;
-
-            // we only care about the "remainder" modulo the
-            // period (the bus ID), so we can +/- period with
-            // impunity.
-            while idx < 0{idx += period}
-            (period, idx)
+            (capture.as_str().parse::<i64>().unwrap(), idx as i64)
         }).collect()
 }
 
+// Solving systems of congruences x = r (mod m), one pair at a time. The
+// moduli don't need to be coprime, as long as they agree wherever they
+// share a factor. Everything is i128 so the combined modulus (the LCM of
+// all of them) can get well past what fits in an i64, and anything past
+// what fits in an i128 is reported rather than wrapped.
+mod crt {
+    use std::fmt;
+
+    // residue mod modulus, with 0 <= residue < modulus
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Congruence {
+        pub residue: i128,
+        pub modulus: i128
+    }
+
+
+    impl Congruence {
+        pub fn new(residue: i128, modulus: i128) -> Congruence {
+            if modulus <= 0 {
+                panic!("modulus must be positive, not {}", modulus);
+            }
+            Congruence { residue: residue.rem_euclid(modulus), modulus }
+        }
+    }
+
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum CrtError {
+        // the congruences contradict each other
+        NoSolution,
+        // the combined modulus doesn't fit in an i128
+        Overflow
+    }
+
+
+    impl fmt::Display for CrtError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                CrtError::NoSolution => write!(f, "no time lines the buses up like that"),
+                CrtError::Overflow => write!(f, "the buses take too long to line up to count in an i128")
+            }
+        }
+    }
+
+
+    // (a * b) mod modulus for 0 <= a, b < modulus, by doubling and adding
+    // so that nothing on the way can overflow
+    pub fn mul_mod(mut a: i128, mut b: i128, modulus: i128) -> i128 {
+        let add_mod = |x: i128, y: i128| if x >= modulus - y { x - (modulus - y) } else { x + y };
+        let mut product: i128 = 0;
+        while b > 0 {
+            if b & 1 == 1 {
+                product = add_mod(product, a);
+            }
+            a = add_mod(a, a);
+            b >>= 1;
+        }
+        product
+    }
+
+    // (g, x, y) where g = gcd(a, b) and a * x + b * y = g
+    pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+        let (mut old_r, mut r) = (a, b);
+        let (mut old_x, mut x) = (1, 0);
+        let (mut old_y, mut y) = (0, 1);
+        while r != 0 {
+            let quotient = old_r / r;
+            let next_r = old_r - quotient * r;
+            old_r = r;
+            r = next_r;
+            let next_x = old_x - quotient * x;
+            old_x = x;
+            x = next_x;
+            let next_y = old_y - quotient * y;
+            old_y = y;
+            y = next_y;
+        }
+        (old_r, old_x, old_y)
+    }
+
+    // one combination of two congruences, with its working
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Step {
+        pub first: Congruence,
+        pub second: Congruence,
+        pub gcd: i128,
+        // first.modulus * bezout.0 + second.modulus * bezout.1 = gcd
+        pub bezout: (i128, i128),
+        pub combined: Result<Congruence, CrtError>
+    }
+
+    // the single congruence that holds exactly when both of these do,
+    // modulo the LCM of the two moduli (if there is one)
+    pub fn combine_step(first: &Congruence, second: &Congruence) -> Step {
+        let (gcd, p, q) = extended_gcd(first.modulus, second.modulus);
+        let mut step: Step = Step {
+            first: *first, second: *second, gcd, bezout: (p, q), combined: Err(CrtError::NoSolution)
+        };
+        let difference: i128 = second.residue - first.residue;
+        if difference % gcd != 0 {
+            return step;
+        }
+        /*
+            first.modulus * p = gcd (mod second.modulus), so adding
+            first.modulus * p * difference / gcd to first.residue moves
+            it onto second.residue without leaving first's class. Only
+            that multiple modulo second.modulus / gcd matters, so it's
+            reduced before multiplying to keep everything in range. The
+            new modulus (the LCM) can still be too big, and then so could
+            the new residue.
+        */
+        let step_modulus: i128 = second.modulus / gcd;
+        let n_steps: i128 = mul_mod(
+            (difference / gcd).rem_euclid(step_modulus), p.rem_euclid(step_modulus), step_modulus
+        );
+        let modulus: Option<i128> = first.modulus.checked_mul(step_modulus);
+        let residue: Option<i128> = n_steps.checked_mul(first.modulus)
+            .and_then(|offset| first.residue.checked_add(offset));
+        step.combined = match (residue, modulus) {
+            (Some(residue), Some(modulus)) => Ok(Congruence::new(residue, modulus)),
+            _ => Err(CrtError::Overflow)
+        };
+        step
+    }
+
+    // an error if the congruences contradict each other or their combined
+    // modulus overflows; x = 0 (mod 1) if there aren't any
+    pub fn solve(congruences: &[Congruence]) -> Result<Congruence, CrtError> {
+        solve_with_steps(congruences).0
+    }
+
+    // solve, along with every combination made on the way, up to and
+    // including the one that failed if there's no solution
+    pub fn solve_with_steps(congruences: &[Congruence]) -> (Result<Congruence, CrtError>, Vec<Step>) {
+        let mut steps: Vec<Step> = vec![];
+        let mut solution: Congruence = match congruences.first() {
+            Some(congruence) => *congruence,
+            None => return (Ok(Congruence::new(0, 1)), steps)
+        };
+        for congruence in congruences[1..].iter() {
+            let step: Step = combine_step(&solution, congruence);
+            steps.push(step);
+            match step.combined {
+                Ok(combined) => solution = combined,
+                Err(error) => return (Err(error), steps)
+            }
+        }
+        (Ok(solution), steps)
+    }
+}
+
 struct AocBufReader {
     iter: Lines<BufReader<File>>,
 }
@@ -95,9 +298,105 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crt::Congruence;
+
+    fn departure_time(line: &str) -> Result<i128, crt::CrtError> {
+        let lines: Vec<String> = vec!["0".to_string(), line.to_string()];
+        find_departure_time(&parse_input(Box::new(lines.into_iter())))
+    }
+
+    #[test]
+    fn test_extended_gcd() {
+        assert_eq!(crt::extended_gcd(240, 46), (2, -9, 47));
+        assert_eq!(crt::extended_gcd(17, 5), (1, -2, 7));
+        assert_eq!(crt::extended_gcd(7, 0), (7, 1, 0));
+    }
+
+    #[test]
+    fn test_combine() {
+        // coprime moduli
+        let solution = crt::combine_step(&Congruence::new(2, 3), &Congruence::new(3, 5)).combined;
+        assert_eq!(solution, Ok(Congruence::new(8, 15)));
+
+        // moduli sharing a factor of 2 that agree, and that don't
+        let solution = crt::combine_step(&Congruence::new(3, 4), &Congruence::new(5, 6)).combined;
+        assert_eq!(solution, Ok(Congruence::new(11, 12)));
+        assert_eq!(crt::combine_step(&Congruence::new(3, 4), &Congruence::new(4, 6)).combined, Err(crt::CrtError::NoSolution));
+
+        let solution = crt::solve(&[Congruence::new(-1, 10), Congruence::new(4, 15), Congruence::new(1, 6)]);
+        assert_eq!(solution, Ok(Congruence::new(19, 30)));
+        assert_eq!(crt::solve(&[]), Ok(Congruence::new(0, 1)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(crt::mul_mod(6, 7, 10), 2);
+        assert_eq!(crt::mul_mod(i128::MAX - 1, i128::MAX - 2, i128::MAX), 2);
+
+        // the first two moduli multiply to just under 2^126, the third
+        // takes the LCM past an i128
+        let congruences: Vec<Congruence> = congruences(&[
+            (9223372036854775783, 0), (9223372036854775643, 2), (9223372036854775549, 3)
+        ]);
+        let (solution, steps) = crt::solve_with_steps(&congruences);
+        assert_eq!(solution, Err(crt::CrtError::Overflow));
+        assert!(steps[0].combined.is_ok());
+        assert_eq!(steps[1].combined, Err(crt::CrtError::Overflow));
+        assert_eq!(
+            departure_time("9223372036854775783,x,9223372036854775643,9223372036854775549"),
+            Err(crt::CrtError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_departure_time() {
+        assert_eq!(departure_time("7,13,x,x,59,x,31,19"), Ok(1068781));
+        assert_eq!(departure_time("17,x,13,19"), Ok(3417));
+        assert_eq!(departure_time("67,7,59,61"), Ok(754018));
+        assert_eq!(departure_time("1789,37,47,1889"), Ok(1202161486));
+
+        // if the 4 bus leaves at t then t is even, so the 2 bus can't
+        // leave at t + 1
+        assert_eq!(departure_time("4,2,4"), Err(crt::CrtError::NoSolution));
+    }
+
+    #[test]
+    fn test_explain() {
+        let buses: Vec<(i64, i64)> = vec![(17, 0), (13, 2), (19, 3)];
+        assert_eq!(explain_departure_time(&buses), [
+            "congruences:",
+            "  bus 17 leaves at t + 0: t \u{2261} 0 (mod 17)",
+            "  bus 13 leaves at t + 2: t \u{2261} 11 (mod 13)",
+            "  bus 19 leaves at t + 3: t \u{2261} 16 (mod 19)",
+            "combining:",
+            "  t \u{2261} 0 (mod 17) and t \u{2261} 11 (mod 13)",
+            "    gcd(17, 13) = 1 = 17 * -3 + 13 * 4",
+            "    => t \u{2261} 102 (mod 221)",
+            "  t \u{2261} 102 (mod 221) and t \u{2261} 16 (mod 19)",
+            "    gcd(221, 19) = 1 = 221 * 8 + 19 * -93",
+            "    => t \u{2261} 3417 (mod 4199)",
+            "verification of t = 3417:",
+            "  t + 0 = 3417 = 17 * 201 ok",
+            "  t + 2 = 3419 = 13 * 263 ok",
+            "  t + 3 = 3420 = 19 * 180 ok",
+            ""
+        ].join("\n"));
+
+        let (solution, steps) = crt::solve_with_steps(&congruences(&[(4, 0), (3, 1), (4, 2)]));
+        assert_eq!(solution, Err(crt::CrtError::NoSolution));
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[1].combined, Err(crt::CrtError::NoSolution));
+        assert!(explain_departure_time(&[(4, 0), (4, 2)]).ends_with("=> no solution: 2 - 0 isn't a multiple of 4\n"));
+    }
+}
@@ -1,21 +1,56 @@
-use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::io::{self, BufRead, BufReader, Lines};
 
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
+    let (source, metrics, show_histogram) = parse_args(&args[1..]);
 
-    let mut aoc_buf_reader: AocBufReader = AocBufReader::from_file(open_file(filename));
-    let total_sum: i32 = aoc_buf_reader.map(|lines| count_chars(&lines)).sum();
-    println!("Unique questions answered yes: {}", total_sum);
+    // groups are tallied as they stream past, so stdin and pipes work
+    // just as well as files and nothing is ever read twice.
+    let aoc_buf_reader: AocBufReader = match source {
+        Some(filename) => AocBufReader::from_file(open_file(&filename)),
+        None => AocBufReader::from_stdin()
+    };
+    let mut alphabet: Alphabet = Alphabet::new();
+    let mut tally: SurveyTally = SurveyTally::new(metrics);
+    for lines in aoc_buf_reader {
+        tally.add_group(&Group::from_lines(&lines, &mut alphabet));
+    }
 
-    aoc_buf_reader = AocBufReader::from_file(open_file(filename));
-    let total_shared: i32 = aoc_buf_reader.map(|lines| count_shared_chars(&lines)).sum();
-    println!("Shared questions answered yes: {}", total_shared);
+    for (metric, total) in tally.metrics.iter().zip(tally.totals.iter()) {
+        println!("{}: {}", metric.label(), total);
+    }
+    if show_histogram {
+        for (question, count) in tally.histogram(&alphabet) {
+            println!("{}: {}", question, count);
+        }
+    }
+}
 
+
+fn parse_args(args: &[String]) -> (Option<String>, Vec<GroupMetric>, bool) {
+    let mut source: Option<String> = None;
+    let mut metrics: Vec<GroupMetric> = vec![];
+    let mut show_histogram: bool = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--metric" => metrics.push(
+                GroupMetric::parse(args.next().expect("--metric needs a value"))
+            ),
+            "--histogram" => show_histogram = true,
+            "-" => source = None,
+            _ => source = Some(arg.to_string())
+        }
+    }
+    if metrics.is_empty() {
+        metrics = vec![GroupMetric::Unique, GroupMetric::Shared];
+    }
+
+    (source, metrics, show_histogram)
 }
 
 
@@ -25,14 +60,22 @@ fn open_file(file_path: &str) -> File {
 
 
 struct AocBufReader {
-    iter: Lines<BufReader<File>>,
+    iter: Lines<Box<dyn BufRead>>,
 }
 
 
 impl AocBufReader {
     fn from_file(file_handle: File) -> AocBufReader {
+        AocBufReader::from_reader(Box::new(BufReader::new(file_handle)))
+    }
+
+    fn from_stdin() -> AocBufReader {
+        AocBufReader::from_reader(Box::new(BufReader::new(io::stdin())))
+    }
+
+    fn from_reader(reader: Box<dyn BufRead>) -> AocBufReader {
         AocBufReader {
-            iter: BufReader::new(file_handle).lines()
+            iter: reader.lines()
         }
     }
 }
@@ -47,16 +90,16 @@ impl Iterator for AocBufReader {
             match self.iter.next() {
                 Some(result) => match result {
                     Ok(line) => {
-                        if line == "" {
+                        if line.is_empty() {
                             break Some(lines)
                         } else {
                             lines.push(line)
                         }
                     },
-                    Err(error) => panic!(error)
+                    Err(error) => panic!("{}", error)
                 },
                 None => {
-                    if lines.len() != 0 {
+                    if !lines.is_empty() {
                         break Some(lines)
                     } else {
                         break None
@@ -68,35 +111,278 @@ impl Iterator for AocBufReader {
 }
 
 
-fn count_chars(lines: &Vec<String>) -> i32 {
-    let mut chars: HashSet<char> = HashSet::new();
-    for line in lines {
-        for c in line.chars() {
-            chars.insert(c);
+// Questions a-z get fixed indices 0..26 so that a single person's answers
+// fit in a u32. Anything else seen in the input is appended after them.
+const NARROW_BITS: usize = 32;
+
+
+struct Alphabet {
+    chars: Vec<char>
+}
+
+
+impl Alphabet {
+    fn new() -> Alphabet {
+        Alphabet { chars: ('a'..='z').collect() }
+    }
+
+    fn index_of(&mut self, c: char) -> usize {
+        match self.chars.iter().position(|known| *known == c) {
+            Some(idx) => idx,
+            None => {
+                self.chars.push(c);
+                self.chars.len() - 1
+            }
         }
     }
-    chars.len() as i32
+
+    fn char_at(&self, idx: usize) -> char {
+        self.chars[idx]
+    }
+
+    fn len(&self) -> usize {
+        self.chars.len()
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AnswerSet {
+    Narrow(u32),
+    Wide(Vec<u64>)
 }
 
 
-fn count_shared_chars(lines: &Vec<String>) -> i32 {
-    let n_passengers: i32 = lines.len() as i32;
+impl AnswerSet {
+    fn empty() -> AnswerSet {
+        AnswerSet::Narrow(0)
+    }
 
-    let mut chars: HashMap<char, i32> = HashMap::new();
-    for line in lines {
+    fn from_line(line: &str, alphabet: &mut Alphabet) -> AnswerSet {
+        let mut answers: AnswerSet = AnswerSet::empty();
         for c in line.chars() {
-            *chars.entry(c).or_insert(0) += 1;
+            answers.insert(alphabet.index_of(c));
         }
+        answers
     }
 
-    chars.iter().map(
-        |(c, n)| {
-            if *n == n_passengers {
-                return 1
+    fn from_words(words: Vec<u64>) -> AnswerSet {
+        // collapse back to the narrow form whenever it fits
+        if words.iter().skip(1).all(|word| *word == 0)
+        && words.first().map_or(0, |word| *word) >> NARROW_BITS == 0 {
+            return AnswerSet::Narrow(words.first().map_or(0, |word| *word) as u32);
+        }
+        AnswerSet::Wide(words)
+    }
+
+    fn to_words(&self) -> Vec<u64> {
+        match self {
+            AnswerSet::Narrow(bits) => vec![*bits as u64],
+            AnswerSet::Wide(words) => words.clone()
+        }
+    }
+
+    fn insert(&mut self, idx: usize) {
+        match self {
+            AnswerSet::Narrow(bits) if idx < NARROW_BITS => *bits |= 1 << idx,
+            _ => {
+                let mut words: Vec<u64> = self.to_words();
+                if words.len() <= idx / 64 {
+                    words.resize(idx / 64 + 1, 0);
+                }
+                words[idx / 64] |= 1 << (idx % 64);
+                *self = AnswerSet::Wide(words);
+            }
+        }
+    }
+
+    fn contains(&self, idx: usize) -> bool {
+        match self {
+            AnswerSet::Narrow(bits) => idx < NARROW_BITS && bits & (1 << idx) != 0,
+            AnswerSet::Wide(words) => {
+                idx / 64 < words.len() && words[idx / 64] & (1 << (idx % 64)) != 0
             }
-            return 0
         }
-    ).sum()
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            AnswerSet::Narrow(bits) => bits.count_ones() as usize,
+            AnswerSet::Wide(words) => words.iter().map(|word| word.count_ones() as usize).sum()
+        }
+    }
+
+    fn indices(&self) -> Vec<usize> {
+        let n_bits: usize = match self {
+            AnswerSet::Narrow(_) => NARROW_BITS,
+            AnswerSet::Wide(words) => words.len() * 64
+        };
+        (0..n_bits).filter(|idx| self.contains(*idx)).collect()
+    }
+
+    fn combine(&self, other: &AnswerSet, op: fn(u64, u64) -> u64) -> AnswerSet {
+        if let (AnswerSet::Narrow(a), AnswerSet::Narrow(b)) = (self, other) {
+            return AnswerSet::Narrow(op(*a as u64, *b as u64) as u32);
+        }
+        let mut a: Vec<u64> = self.to_words();
+        let mut b: Vec<u64> = other.to_words();
+        let n_words: usize = a.len().max(b.len());
+        a.resize(n_words, 0);
+        b.resize(n_words, 0);
+        AnswerSet::from_words(a.iter().zip(b.iter()).map(|(x, y)| op(*x, *y)).collect())
+    }
+
+    fn union(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a | b)
+    }
+
+    fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        self.combine(other, |a, b| a & b)
+    }
+}
+
+
+struct Group {
+    members: Vec<AnswerSet>
+}
+
+
+impl Group {
+    fn from_lines(lines: &[String], alphabet: &mut Alphabet) -> Group {
+        Group {
+            members: lines.iter().map(|line| AnswerSet::from_line(line, alphabet)).collect()
+        }
+    }
+
+    fn union(&self) -> AnswerSet {
+        self.members.iter().fold(AnswerSet::empty(), |acc, member| acc.union(member))
+    }
+
+    fn intersection(&self) -> AnswerSet {
+        let mut members = self.members.iter();
+        match members.next() {
+            Some(first) => members.fold(first.clone(), |acc, member| acc.intersection(member)),
+            None => AnswerSet::empty()
+        }
+    }
+
+    // how many members answered each question, indexed like the alphabet
+    fn frequencies(&self) -> Vec<usize> {
+        let mut counts: Vec<usize> = vec![];
+        for member in &self.members {
+            for idx in member.indices() {
+                if counts.len() <= idx {
+                    counts.resize(idx + 1, 0);
+                }
+                counts[idx] += 1;
+            }
+        }
+        counts
+    }
+
+    fn matching_frequency(&self, keep: impl Fn(usize) -> bool) -> AnswerSet {
+        let mut answers: AnswerSet = AnswerSet::empty();
+        for (idx, count) in self.frequencies().iter().enumerate() {
+            if *count > 0 && keep(*count) {
+                answers.insert(idx);
+            }
+        }
+        answers
+    }
+
+    // questions nobody in the group answered are never counted, even for k = 0
+    fn at_least(&self, k: usize) -> AnswerSet {
+        self.matching_frequency(|count| count >= k)
+    }
+
+    fn exactly(&self, k: usize) -> AnswerSet {
+        self.matching_frequency(|count| count == k)
+    }
+
+    fn exactly_one(&self) -> AnswerSet {
+        self.exactly(1)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupMetric {
+    Unique,
+    Shared,
+    AtLeast(usize),
+    Exactly(usize)
+}
+
+
+impl GroupMetric {
+    fn parse(name: &str) -> GroupMetric {
+        let (name, k): (&str, Option<usize>) = match name.split_once(':') {
+            Some((name, k)) => (name, Some(k.parse::<usize>().expect("metric size must be a number"))),
+            None => (name, None)
+        };
+        match (name, k) {
+            ("unique", None) => GroupMetric::Unique,
+            ("shared", None) => GroupMetric::Shared,
+            ("exactly-one", None) => GroupMetric::Exactly(1),
+            ("at-least", Some(k)) => GroupMetric::AtLeast(k),
+            ("exactly", Some(k)) => GroupMetric::Exactly(k),
+            _ => panic!("unknown metric {}", name)
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            GroupMetric::Unique => "Unique questions answered yes".to_string(),
+            GroupMetric::Shared => "Shared questions answered yes".to_string(),
+            GroupMetric::Exactly(1) => "Questions answered yes by exactly one person".to_string(),
+            GroupMetric::AtLeast(k) => format!("Questions answered yes by at least {} people", k),
+            GroupMetric::Exactly(k) => format!("Questions answered yes by exactly {} people", k)
+        }
+    }
+
+    fn evaluate(&self, group: &Group) -> usize {
+        match self {
+            GroupMetric::Unique => group.union().len(),
+            GroupMetric::Shared => group.intersection().len(),
+            GroupMetric::AtLeast(k) => group.at_least(*k).len(),
+            GroupMetric::Exactly(1) => group.exactly_one().len(),
+            GroupMetric::Exactly(k) => group.exactly(*k).len()
+        }
+    }
+}
+
+
+struct SurveyTally {
+    metrics: Vec<GroupMetric>,
+    totals: Vec<usize>,
+    // how many people answered each question, summed over every group
+    question_counts: Vec<usize>
+}
+
+
+impl SurveyTally {
+    fn new(metrics: Vec<GroupMetric>) -> SurveyTally {
+        let totals: Vec<usize> = vec![0; metrics.len()];
+        SurveyTally { metrics, totals, question_counts: vec![] }
+    }
+
+    fn add_group(&mut self, group: &Group) {
+        for (metric, total) in self.metrics.iter().zip(self.totals.iter_mut()) {
+            *total += metric.evaluate(group);
+        }
+        for (idx, count) in group.frequencies().iter().enumerate() {
+            if self.question_counts.len() <= idx {
+                self.question_counts.resize(idx + 1, 0);
+            }
+            self.question_counts[idx] += count;
+        }
+    }
+
+    fn histogram(&self, alphabet: &Alphabet) -> Vec<(char, usize)> {
+        (0..alphabet.len())
+            .map(|idx| (alphabet.char_at(idx), *self.question_counts.get(idx).unwrap_or(&0)))
+            .collect()
+    }
 }
 
 
@@ -105,18 +391,81 @@ fn count_shared_chars(lines: &Vec<String>) -> i32 {
 mod tests {
     use super::*;
 
+    fn group(lines: &[&str]) -> Group {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        Group::from_lines(&lines, &mut Alphabet::new())
+    }
+
     #[test]
     fn test_count_chars() {
-        assert_eq!(count_chars(&vec!["abc".to_string()]), 3);
-        assert_eq!(count_chars(&vec!["a".to_string(), "bc".to_string()]), 3);
-        assert_eq!(count_chars(&vec!["a".to_string(), "a".to_string()]), 1);
+        assert_eq!(GroupMetric::Unique.evaluate(&group(&["abc"])), 3);
+        assert_eq!(GroupMetric::Unique.evaluate(&group(&["a", "bc"])), 3);
+        assert_eq!(GroupMetric::Unique.evaluate(&group(&["a", "a"])), 1);
     }
 
     #[test]
     fn test_count_shared_chars() {
-        assert_eq!(count_shared_chars(&vec!["abc".to_string()]), 3);
-        assert_eq!(count_shared_chars(&vec!["a".to_string(), "bc".to_string()]), 0);
-        assert_eq!(count_shared_chars(&vec!["a".to_string(), "a".to_string()]), 1);
+        assert_eq!(GroupMetric::Shared.evaluate(&group(&["abc"])), 3);
+        assert_eq!(GroupMetric::Shared.evaluate(&group(&["a", "bc"])), 0);
+        assert_eq!(GroupMetric::Shared.evaluate(&group(&["a", "a"])), 1);
     }
-}
 
+    #[test]
+    fn test_answer_set_ops() {
+        let mut alphabet = Alphabet::new();
+        let ab = AnswerSet::from_line("ab", &mut alphabet);
+        let bc = AnswerSet::from_line("bc", &mut alphabet);
+        assert_eq!(ab, AnswerSet::Narrow(0b011));
+        assert_eq!(ab.union(&bc), AnswerSet::Narrow(0b111));
+        assert_eq!(ab.intersection(&bc), AnswerSet::Narrow(0b010));
+    }
+
+    #[test]
+    fn test_wide_answer_set() {
+        let mut alphabet = Alphabet::new();
+        let line: String = ('a'..='z').chain("0123456789".chars()).collect();
+        let wide = AnswerSet::from_line(&line, &mut alphabet);
+        assert!(matches!(wide, AnswerSet::Wide(_)));
+        assert_eq!(wide.len(), 36);
+        assert!(wide.contains(alphabet.index_of('9')));
+
+        // intersecting back down to a-z gives the narrow form again
+        let narrow = AnswerSet::from_line("xyz", &mut alphabet);
+        assert_eq!(wide.intersection(&narrow), narrow);
+    }
+
+    #[test]
+    fn test_group_counts() {
+        let mut alphabet = Alphabet::new();
+        let lines: Vec<String> = vec!["abc".to_string(), "ab".to_string(), "a".to_string()];
+        let group = Group::from_lines(&lines, &mut alphabet);
+        assert_eq!(group.union().len(), 3);
+        assert_eq!(group.intersection().len(), 1);
+        assert_eq!(group.at_least(2).indices(), vec![0, 1]);
+        assert_eq!(group.exactly_one().indices(), vec![2]);
+        assert_eq!(group.frequencies(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_parse_metric() {
+        assert_eq!(GroupMetric::parse("unique"), GroupMetric::Unique);
+        assert_eq!(GroupMetric::parse("exactly-one"), GroupMetric::Exactly(1));
+        assert_eq!(GroupMetric::parse("at-least:3"), GroupMetric::AtLeast(3));
+    }
+
+    #[test]
+    fn test_single_pass_tally() {
+        let input: &[u8] = b"abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb\n";
+        let mut alphabet = Alphabet::new();
+        let mut tally = SurveyTally::new(vec![GroupMetric::Unique, GroupMetric::Shared, GroupMetric::Exactly(1)]);
+        for lines in AocBufReader::from_reader(Box::new(input)) {
+            tally.add_group(&Group::from_lines(&lines, &mut alphabet));
+        }
+        assert_eq!(tally.totals, vec![11, 6, 9]);
+
+        let histogram = tally.histogram(&alphabet);
+        assert_eq!(histogram[0], ('a', 8));
+        assert_eq!(histogram[1], ('b', 4));
+        assert_eq!(histogram[3], ('d', 0));
+    }
+}
@@ -1,8 +1,12 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::fs::File;
+use std::fmt;
+use std::fs;
 use std::io::{BufRead, BufReader, Lines};
+use std::process;
 
+use lazy_static::lazy_static;
 use regex::Regex;
 
 const SHINY_GOLD: &str = "shiny gold";
@@ -10,16 +14,62 @@ const SHINY_GOLD: &str = "shiny gold";
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+    let mut validation: Validation = Validation::Lenient;
+    let mut export: Option<ExportFormat> = None;
+    let mut subgraph: Subgraph = Subgraph::All;
+    let mut output: Option<String> = None;
+
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let mut value = || options.next().unwrap_or_else(|| panic!("{} needs a value", option)).to_string();
+        match option.as_str() {
+            "--strict" => validation = Validation::Strict,
+            "--lenient" => validation = Validation::Lenient,
+            "--export" => export = Some(ExportFormat::parse(&value())),
+            "--from" => subgraph = Subgraph::From(value()),
+            "--to" => subgraph = Subgraph::To(value()),
+            "--output" => output = Some(value()),
+            other => panic!("unknown option {}", other)
+        }
+    }
 
     let aoc_buf_reader: AocBufReader = AocBufReader::from_file(open_file(filename));
-    let bag_map = parse_bags(Box::new(aoc_buf_reader));
-    let valid_bags = find_valid_bags(&bag_map, SHINY_GOLD);
-    println!("{} different bags can hold your bag!", valid_bags.len());
+    let bag_graph: BagGraph = match BagGraph::from_lines(Box::new(aoc_buf_reader), validation) {
+        Ok(bag_graph) => bag_graph,
+        Err(problems) => {
+            for problem in problems {
+                eprintln!("error: {}", problem);
+            }
+            process::exit(1);
+        }
+    };
+
+    if let Some(format) = export {
+        let nodes: Vec<usize> = match bag_graph.select(&subgraph) {
+            Some(nodes) => nodes,
+            None => {
+                eprintln!("error: no rules mention {:?}", subgraph);
+                process::exit(1);
+            }
+        };
+        let exported: String = match format {
+            ExportFormat::Dot => bag_graph.to_dot(&nodes),
+            ExportFormat::Json => bag_graph.to_json(&nodes)
+        };
+        match output {
+            Some(path) => fs::write(path, exported).unwrap(),
+            None => print!("{}", exported)
+        }
+        return;
+    }
 
+    let valid_bags = bag_graph.containers_of(SHINY_GOLD);
+    println!("{} different bags can hold your bag!", valid_bags.len());
 
-    let mut bag_count: HashMap<String, i32> = HashMap::new();
-    count_held_bags(&bag_map, &mut bag_count, SHINY_GOLD, 1);
-    println!("you need a whopping {} total bags!", bag_count.values().sum::<i32>());
+    match bag_graph.count_inside(SHINY_GOLD) {
+        Some(count) => println!("you need a whopping {} total bags!", count),
+        None => println!("your bag holds a loop of bags; no finite number will do!")
+    }
 }
 
 
@@ -28,96 +78,409 @@ fn open_file(file_path: &str) -> File {
 }
 
 
-fn parse_line(line: &str) -> (String, Vec<(String, i32)>) {
-    let parent_bag_re: Regex = Regex::new(
-        r"^(.*) bags contain.*"
-    ).unwrap();
-    let parent_bag: String = parent_bag_re.captures(&line).and_then(
-        | capture | Some(capture.get(1).unwrap().as_str())
-    ).unwrap().to_string();
+struct Rule {
+    color: String,
+    capacities: Vec<(String, i32)>,
+    // pieces of the contents list we couldn't make sense of
+    malformed: Vec<String>
+}
 
-    let empty_bag_re: Regex = Regex::new(
-        r"contain no other bags."
-    ).unwrap();
-    let non_empty_bag_re: Regex = Regex::new(
-        "([0-9]+) ([^,0-9]*) bag"
-    ).unwrap();
 
+fn parse_line(line: &str) -> Option<Rule> {
+    lazy_static! {
+        static ref RULE_RE: Regex = Regex::new(
+            r"^(.+) bags contain (.*)$"
+        ).unwrap();
+        static ref CAPACITY_RE: Regex = Regex::new(
+            r"^([0-9]+) (.+) bags?$"
+        ).unwrap();
+    }
+    let (color, contents): (&str, &str) = RULE_RE.captures(line).map(
+        | capture | (capture.get(1).unwrap().as_str(), capture.get(2).unwrap().as_str())
+    )?;
+
+    let mut rule: Rule = Rule {
+        color: color.to_string(),
+        capacities: vec![],
+        malformed: vec![]
+    };
+    if contents == "no other bags." {
+        return Some(rule);
+    }
 
-    let mut capacities: Vec<(String, i32)> = vec![];
-    if empty_bag_re.captures(&line).iter().len() > 0 {
-        return (parent_bag, capacities);
-    } else {
-        let captures = non_empty_bag_re.captures_iter(&line);
-        for capture in captures {
-            let size: i32 = capture.get(1).unwrap().as_str().parse::<i32>().unwrap();
-            let color: &str = capture.get(2).unwrap().as_str();
-            capacities.push((color.to_string(), size));
+    for fragment in contents.trim_end_matches('.').split(", ") {
+        match CAPACITY_RE.captures(fragment) {
+            Some(capture) => {
+                let size: i32 = capture.get(1).unwrap().as_str().parse::<i32>().unwrap();
+                let color: &str = capture.get(2).unwrap().as_str();
+                rule.capacities.push((color.to_string(), size));
+            },
+            None => rule.malformed.push(fragment.to_string())
         }
     }
 
-    (parent_bag, capacities)
+    Some(rule)
 }
 
 
-fn parse_bags(lines: Box<dyn Iterator<Item = String>>) -> HashMap<String, HashMap<String, i32>> {
-    let mut bag_map: HashMap<String, HashMap<String, i32>> = HashMap::new();
-    for line in lines {
-        let (parent_bag_color, capacities) = parse_line(&line);
-        bag_map.insert(parent_bag_color.to_string(), HashMap::new());
-        
-        for (child_bag_color, capacity) in capacities {
-            if !bag_map.contains_key(&child_bag_color) {
-                bag_map.insert(child_bag_color.to_string(), HashMap::new());
-            } 
-            bag_map.get_mut(&parent_bag_color).unwrap().insert(child_bag_color.to_string(), capacity);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Validation {
+    // any problem with the rules is an error
+    Strict,
+    // problems are printed as warnings and the graph is built anyway
+    Lenient
+}
+
+
+#[derive(Debug, PartialEq, Eq)]
+enum RuleProblem {
+    Malformed { line: usize, fragment: String },
+    Duplicate { line: usize, color: String },
+    Undefined(String),
+    Cycle(Vec<String>)
+}
+
+
+impl fmt::Display for RuleProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleProblem::Malformed { line, fragment } => {
+                write!(f, "line {}: can't parse \"{}\"", line, fragment)
+            },
+            RuleProblem::Duplicate { line, color } => {
+                write!(f, "line {}: {} bags were already defined", line, color)
+            },
+            RuleProblem::Undefined(color) => {
+                write!(f, "{} bags are held by other bags but never defined", color)
+            },
+            RuleProblem::Cycle(chain) => {
+                write!(f, "bags hold themselves: {}", chain.join(" -> "))
+            }
         }
     }
+}
+
 
-    bag_map
+// Colors are interned to dense ids so that both directions of the
+// containment relation are just vectors of edges.
+struct BagGraph {
+    colors: Vec<String>,
+    ids: HashMap<String, usize>,
+    contents: Vec<Vec<(usize, i32)>>,
+    containers: Vec<Vec<(usize, i32)>>
 }
 
 
-fn add_bags(old_set: &mut HashSet<String>, new_bags: Vec<String>) {
-    for new_bag in new_bags {
-        old_set.insert(new_bag);
+impl BagGraph {
+    fn new() -> BagGraph {
+        BagGraph {
+            colors: vec![],
+            ids: HashMap::new(),
+            contents: vec![],
+            containers: vec![]
+        }
+    }
+
+    fn from_lines(lines: Box<dyn Iterator<Item = String>>, validation: Validation) -> Result<BagGraph, Vec<RuleProblem>> {
+        let mut bag_graph: BagGraph = BagGraph::new();
+        let mut defined: HashSet<String> = HashSet::new();
+        let mut problems: Vec<RuleProblem> = vec![];
+
+        for (idx, line) in lines.enumerate().filter(|(_idx, line)| !line.trim().is_empty()) {
+            let rule: Rule = match parse_line(&line) {
+                Some(rule) => rule,
+                None => {
+                    problems.push(RuleProblem::Malformed { line: idx + 1, fragment: line });
+                    continue;
+                }
+            };
+            for fragment in rule.malformed {
+                problems.push(RuleProblem::Malformed { line: idx + 1, fragment });
+            }
+            // the first definition of a color wins
+            if !defined.insert(rule.color.to_string()) {
+                problems.push(RuleProblem::Duplicate { line: idx + 1, color: rule.color });
+                continue;
+            }
+            bag_graph.add_rule(&rule.color, &rule.capacities);
+        }
+
+        for color in &bag_graph.colors {
+            if !defined.contains(color) {
+                problems.push(RuleProblem::Undefined(color.to_string()));
+            }
+        }
+        problems.extend(bag_graph.find_cycles().into_iter().map(RuleProblem::Cycle));
+
+        if problems.is_empty() {
+            return Ok(bag_graph);
+        }
+        match validation {
+            Validation::Strict => Err(problems),
+            Validation::Lenient => {
+                for problem in problems {
+                    eprintln!("warning: {}", problem);
+                }
+                Ok(bag_graph)
+            }
+        }
+    }
+
+    fn intern(&mut self, color: &str) -> usize {
+        if let Some(id) = self.ids.get(color) {
+            return *id;
+        }
+        let id: usize = self.colors.len();
+        self.colors.push(color.to_string());
+        self.ids.insert(color.to_string(), id);
+        self.contents.push(vec![]);
+        self.containers.push(vec![]);
+        id
+    }
+
+    fn id(&self, color: &str) -> Option<usize> {
+        self.ids.get(color).copied()
     }
-}
 
+    fn color(&self, id: usize) -> &str {
+        &self.colors[id]
+    }
 
-fn find_valid_bags(bag_map: &HashMap<String, HashMap<String, i32>>, color: &str) -> HashSet<String> {
-    let mut valid_bags: HashSet<String> = HashSet::new();
+    fn add_rule(&mut self, parent_bag_color: &str, capacities: &[(String, i32)]) {
+        let parent: usize = self.intern(parent_bag_color);
+        for (child_bag_color, capacity) in capacities {
+            let child: usize = self.intern(child_bag_color);
+            self.contents[parent].push((child, *capacity));
+            self.containers[child].push((parent, *capacity));
+        }
+    }
 
-    for (bag_color, sub_bags) in bag_map {
-        if sub_bags.contains_key(color) {
-            valid_bags.insert(bag_color.to_string());
+    // every bag that can eventually hold a bag of this color,
+    // found by a breadth-first walk up the reverse edges
+    fn containers_of(&self, color: &str) -> HashSet<String> {
+        match self.id(color) {
+            Some(id) => {
+                // with a cycle the walk comes back round to the bag itself,
+                // which doesn't count as holding itself
+                let mut seen: HashSet<usize> = self.walk(id, &self.containers);
+                seen.remove(&id);
+                seen.iter().map(|id| self.color(*id).to_string()).collect()
+            },
+            None => HashSet::new()
         }
     }
 
-    let mut prior_len: usize = 0;
-    while prior_len != valid_bags.len() {
-        prior_len = valid_bags.len();
-        let mut new_bags: Vec<String> = vec![];
-        for valid_bag in &valid_bags {
-            for (bag_color, sub_bags) in bag_map {
-                if sub_bags.contains_key(valid_bag) {
-                    new_bags.push(bag_color.to_string());
+    fn walk(&self, start: usize, edges: &[Vec<(usize, i32)>]) -> HashSet<usize> {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut queue: VecDeque<usize> = VecDeque::from(vec![start]);
+        while let Some(id) = queue.pop_front() {
+            for (next, _capacity) in &edges[id] {
+                if seen.insert(*next) {
+                    queue.push_back(*next);
                 }
             }
         }
-        add_bags(&mut valid_bags, new_bags);
+        seen
     }
 
-    valid_bags
+    // the ids to export, in the order they were first seen in the rules
+    fn select(&self, subgraph: &Subgraph) -> Option<Vec<usize>> {
+        let mut nodes: Vec<usize> = match subgraph {
+            Subgraph::All => return Some((0..self.colors.len()).collect()),
+            Subgraph::From(color) => {
+                let id: usize = self.id(color)?;
+                self.walk(id, &self.contents).into_iter().chain(std::iter::once(id)).collect()
+            },
+            Subgraph::To(color) => {
+                let id: usize = self.id(color)?;
+                self.walk(id, &self.containers).into_iter().chain(std::iter::once(id)).collect()
+            }
+        };
+        nodes.sort_unstable();
+        nodes.dedup();
+        Some(nodes)
+    }
+
+    fn to_dot(&self, nodes: &[usize]) -> String {
+        let included: HashSet<usize> = nodes.iter().copied().collect();
+        let mut dot: String = "digraph bags {\n".to_string();
+        for id in nodes {
+            dot.push_str(&format!("    {};\n", dot_quote(self.color(*id))));
+        }
+        for id in nodes {
+            for (child, capacity) in self.contents[*id].iter().filter(|(child, _)| included.contains(child)) {
+                dot.push_str(&format!(
+                    "    {} -> {} [label=\"{}\"];\n",
+                    dot_quote(self.color(*id)),
+                    dot_quote(self.color(*child)),
+                    capacity
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // {"color": {"held color": capacity, ...}, ...}
+    fn to_json(&self, nodes: &[usize]) -> String {
+        let included: HashSet<usize> = nodes.iter().copied().collect();
+        let entries: Vec<String> = nodes.iter().map(|id| {
+            let children: Vec<String> = self.contents[*id].iter()
+                .filter(|(child, _)| included.contains(child))
+                .map(|(child, capacity)| format!("{}: {}", json_quote(self.color(*child)), capacity))
+                .collect();
+            format!("  {}: {{{}}}", json_quote(self.color(*id)), children.join(", "))
+        }).collect();
+        format!("{{\n{}\n}}\n", entries.join(",\n"))
+    }
+
+    // Depth first search over the contents edges. Every edge back onto
+    // the current path closes a loop, which is reported as the chain of
+    // colors from the repeated bag back around to itself. The rule sets can
+    // nest far deeper than the call stack, so the search keeps its own.
+    fn find_cycles(&self) -> Vec<Vec<String>> {
+        let mut visit: Vec<Visit> = vec![Visit::Unvisited; self.colors.len()];
+        let mut path: Vec<usize> = vec![];
+        let mut cycles: Vec<Vec<String>> = vec![];
+        // (bag, index of its next child to look at) for each bag on the path
+        let mut stack: Vec<(usize, usize)> = vec![];
+        for start in 0..self.colors.len() {
+            if visit[start] != Visit::Unvisited {
+                continue;
+            }
+            visit[start] = Visit::InProgress;
+            path.push(start);
+            stack.push((start, 0));
+
+            while let Some((id, next_child)) = stack.last_mut() {
+                match self.contents[*id].get(*next_child) {
+                    Some((child, _capacity)) => {
+                        *next_child += 1;
+                        match visit[*child] {
+                            Visit::Unvisited => {
+                                visit[*child] = Visit::InProgress;
+                                path.push(*child);
+                                stack.push((*child, 0));
+                            },
+                            Visit::InProgress => {
+                                let start: usize = path.iter().position(|on_path| on_path == child).unwrap();
+                                cycles.push(
+                                    path[start..].iter().chain(std::iter::once(child))
+                                        .map(|id| self.color(*id).to_string())
+                                        .collect()
+                                );
+                            },
+                            Visit::Done(_) => ()
+                        }
+                    },
+                    None => {
+                        visit[*id] = Visit::Done(None);
+                        path.pop();
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        cycles
+    }
+
+    // total bags held inside a bag of this color (not counting itself),
+    // or None if it holds a loop of bags and so never bottoms out
+    fn count_inside(&self, color: &str) -> Option<u64> {
+        let start: usize = match self.id(color) {
+            Some(id) => id,
+            None => return Some(0)
+        };
+        let mut visit: Vec<Visit> = vec![Visit::Unvisited; self.colors.len()];
+        // (bag, index of its next child to add in, total so far); a bag's
+        // children are all counted before it is, without recursing
+        let mut stack: Vec<(usize, usize, Option<u64>)> = vec![(start, 0, Some(0))];
+        visit[start] = Visit::InProgress;
+
+        while let Some((id, next_child, count)) = stack.last_mut() {
+            match self.contents[*id].get(*next_child) {
+                Some((child, capacity)) => {
+                    let held: Option<u64> = match visit[*child] {
+                        Visit::Done(held) => held,
+                        Visit::InProgress => None,
+                        Visit::Unvisited => {
+                            // come back to this child once it has a count
+                            visit[*child] = Visit::InProgress;
+                            stack.push((*child, 0, Some(0)));
+                            continue;
+                        }
+                    };
+                    *count = count.zip(held).map(|(count, held)| count + *capacity as u64 * (1 + held));
+                    *next_child += 1;
+                },
+                None => {
+                    visit[*id] = Visit::Done(*count);
+                    stack.pop();
+                }
+            }
+        }
+
+        match visit[start] {
+            Visit::Done(count) => count,
+            _ => unreachable!("every bag on the stack is done once it empties")
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Dot,
+    Json
 }
 
 
-fn count_held_bags(bag_map: &HashMap<String, HashMap<String, i32>>, bag_count: &mut HashMap<String, i32>, color: &str, n_bags: i32) {
-    for (parent_bag, capacity) in &bag_map[color] {    
+impl ExportFormat {
+    fn parse(name: &str) -> ExportFormat {
+        match name {
+            "dot" => ExportFormat::Dot,
+            "json" => ExportFormat::Json,
+            _ => panic!("can only export to dot or json, not {}", name)
+        }
+    }
+}
+
 
-        *bag_count.entry(parent_bag.to_string()).or_insert(0) += n_bags * *capacity;
-        count_held_bags(bag_map, bag_count, &parent_bag, capacity * n_bags)
+#[derive(Debug, PartialEq, Eq)]
+enum Subgraph {
+    All,
+    // this color and every bag it eventually holds
+    From(String),
+    // this color and every bag that eventually holds it
+    To(String)
+}
+
+
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+
+fn json_quote(text: &str) -> String {
+    let mut quoted: String = "\"".to_string();
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c)
+        }
     }
+    quoted.push('"');
+    quoted
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Visit {
+    Unvisited,
+    InProgress,
+    Done(Option<u64>)
 }
 
 
@@ -141,9 +504,167 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_graph() -> BagGraph {
+        let lines: Vec<String> = [
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+            "dark orange bags contain 3 bright white bags, 4 muted yellow bags.",
+            "bright white bags contain 1 shiny gold bag.",
+            "muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.",
+            "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.",
+            "dark olive bags contain 3 faded blue bags, 4 dotted black bags.",
+            "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.",
+            "faded blue bags contain no other bags.",
+            "dotted black bags contain no other bags."
+        ].iter().map(|line| line.to_string()).collect();
+        BagGraph::from_lines(Box::new(lines.into_iter()), Validation::Strict).unwrap()
+    }
+
+    #[test]
+    fn test_containers_of() {
+        let bag_graph = example_graph();
+        let containers = bag_graph.containers_of(SHINY_GOLD);
+        assert_eq!(containers.len(), 4);
+        assert!(containers.contains("light red"));
+        assert!(containers.contains("dark orange"));
+        assert!(bag_graph.containers_of("light red").is_empty());
+    }
+
+    #[test]
+    fn test_count_inside() {
+        let bag_graph = example_graph();
+        assert_eq!(bag_graph.count_inside(SHINY_GOLD), Some(32));
+        assert_eq!(bag_graph.count_inside("faded blue"), Some(0));
+        assert_eq!(bag_graph.count_inside("not a bag"), Some(0));
+    }
+
+    fn lines(lines: &[&str]) -> Box<dyn Iterator<Item = String>> {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        Box::new(lines.into_iter())
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let rule = parse_line("light red bags contain 1 bright white bag, 2 muted yellow bags.").unwrap();
+        assert_eq!(rule.color, "light red");
+        assert_eq!(rule.capacities, vec![("bright white".to_string(), 1), ("muted yellow".to_string(), 2)]);
+        assert!(rule.malformed.is_empty());
+
+        let rule = parse_line("light red bags contain 1 bright white bag, some muted yellow bags.").unwrap();
+        assert_eq!(rule.malformed, vec!["some muted yellow bags".to_string()]);
+        assert!(parse_line("light red").is_none());
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let rules = [
+            "shiny gold bags contain 1 dark olive bag.",
+            "dark olive bags contain 2 vibrant plum bags.",
+            "vibrant plum bags contain 1 shiny gold bag, 1 faded blue bag.",
+            "faded blue bags contain no other bags."
+        ];
+        let problems = BagGraph::from_lines(lines(&rules), Validation::Strict).err().unwrap();
+        assert_eq!(problems, vec![RuleProblem::Cycle(vec![
+            "shiny gold".to_string(),
+            "dark olive".to_string(),
+            "vibrant plum".to_string(),
+            "shiny gold".to_string()
+        ])]);
+
+        let bag_graph = BagGraph::from_lines(lines(&rules), Validation::Lenient).unwrap();
+        assert_eq!(bag_graph.count_inside(SHINY_GOLD), None);
+        assert_eq!(bag_graph.count_inside("faded blue"), Some(0));
+
+        let mut containers: Vec<String> = bag_graph.containers_of(SHINY_GOLD).into_iter().collect();
+        containers.sort();
+        assert_eq!(containers, vec!["dark olive".to_string(), "vibrant plum".to_string()]);
+    }
+
+    // one bag inside the next, far deeper than the call stack could recurse
+    fn chain(length: usize) -> Vec<String> {
+        let mut rules: Vec<String> = (0..length)
+            .map(|idx| format!("bag{} bags contain 1 bag{} bag.", idx, idx + 1))
+            .collect();
+        rules.push(format!("bag{} bags contain no other bags.", length));
+        rules
+    }
+
+    #[test]
+    fn test_deep_chain() {
+        let rules: Vec<String> = chain(100_000);
+        let bag_graph = BagGraph::from_lines(Box::new(rules.into_iter()), Validation::Strict).unwrap();
+        assert_eq!(bag_graph.containers_of("bag100000").len(), 100_000);
+        assert_eq!(bag_graph.count_inside("bag0"), Some(100_000));
+    }
+
+    #[test]
+    fn test_undefined_and_duplicate() {
+        let rules = [
+            "shiny gold bags contain 1 dark olive bag.",
+            "shiny gold bags contain no other bags.",
+            "",
+            "dark olive bags contain 3 plaid bags."
+        ];
+        let problems = BagGraph::from_lines(lines(&rules), Validation::Strict).err().unwrap();
+        assert_eq!(problems, vec![
+            RuleProblem::Duplicate { line: 2, color: "shiny gold".to_string() },
+            RuleProblem::Undefined("plaid".to_string())
+        ]);
+
+        // lenient mode keeps the first definition
+        let bag_graph = BagGraph::from_lines(lines(&rules), Validation::Lenient).unwrap();
+        assert_eq!(bag_graph.count_inside(SHINY_GOLD), Some(4));
+    }
+
+    #[test]
+    fn test_select_subgraph() {
+        let bag_graph = example_graph();
+        let colors = |nodes: Vec<usize>| -> Vec<String> {
+            nodes.iter().map(|id| bag_graph.color(*id).to_string()).collect()
+        };
+        assert_eq!(bag_graph.select(&Subgraph::All).unwrap().len(), 9);
+        assert_eq!(
+            colors(bag_graph.select(&Subgraph::From("dark olive".to_string())).unwrap()),
+            vec!["faded blue", "dark olive", "dotted black"]
+        );
+        assert_eq!(
+            colors(bag_graph.select(&Subgraph::To("bright white".to_string())).unwrap()),
+            vec!["light red", "bright white", "dark orange"]
+        );
+        assert!(bag_graph.select(&Subgraph::To("plaid".to_string())).is_none());
+    }
+
+    #[test]
+    fn test_export() {
+        let bag_graph = example_graph();
+        let nodes = bag_graph.select(&Subgraph::From("dark olive".to_string())).unwrap();
+        assert_eq!(bag_graph.to_dot(&nodes), concat!(
+            "digraph bags {\n",
+            "    \"faded blue\";\n",
+            "    \"dark olive\";\n",
+            "    \"dotted black\";\n",
+            "    \"dark olive\" -> \"faded blue\" [label=\"3\"];\n",
+            "    \"dark olive\" -> \"dotted black\" [label=\"4\"];\n",
+            "}\n"
+        ));
+        assert_eq!(bag_graph.to_json(&nodes), concat!(
+            "{\n",
+            "  \"faded blue\": {},\n",
+            "  \"dark olive\": {\"faded blue\": 3, \"dotted black\": 4},\n",
+            "  \"dotted black\": {}\n",
+            "}\n"
+        ));
+        assert_eq!(json_quote("a \"b\""), "\"a \\\"b\\\"\"");
+    }
+}
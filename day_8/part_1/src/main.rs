@@ -1,25 +1,60 @@
+use std::collections::{HashMap, VecDeque};
 use std::env;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
+use std::io::{self, BufRead, BufReader, Lines, Write};
+use std::process;
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
-const UNINITIATED: &str = "NONE";
-const ACC: &str = "acc";
-const JMP: &str = "jmp";
-const NOP: &str = "nop";
-
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
 
-    let n_instructions: usize = count_lines(open_file(filename));
     let aoc_reader = AocBufReader::from_file(open_file(filename));
 
-    let mut instructions: Vec<Instruction> = parse_instructions(Box::new(aoc_reader), n_instructions);
-    let return_val: i32 = debug_instructions(&mut instructions);
-    println!("the final value is {}!", return_val);
+    let program: Vec<Instruction> = match parse_instructions(Box::new(aoc_reader)) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            process::exit(1);
+        }
+    };
+    match args.get(2).map(|arg| arg.as_str()) {
+        Some("--loop") => match find_loop(&program) {
+            Some(report) => {
+                println!("the accumulator is {} before the loop repeats!", report.acc);
+                println!("the loop is entered at instruction {}", report.entry);
+                let cycle: Vec<String> = report.cycle.iter().map(|idx| idx.to_string()).collect();
+                println!("the loop runs instructions {}", cycle.join(" -> "));
+            },
+            None => println!("the program doesn't loop!")
+        },
+        Some("--repair") | None => match (Vm::new(program.clone()).run(), debug_instructions(&program)) {
+            (Termination::Halted(accumulator), _) => {
+                println!("the program already halts with {}, there's nothing to fix!", accumulator)
+            },
+            (_, Some((idx, opcode, accumulator))) => {
+                println!("changing instruction {} to {} fixes the program", idx, opcode.mnemonic());
+                println!("the final value is {}!", accumulator);
+            },
+            (_, None) => println!("no single change fixes the program!")
+        },
+        Some("--repairs") => {
+            for (idx, opcode, accumulator) in find_repairs(&program) {
+                println!("instruction {} -> {}: final value {}", idx, opcode.mnemonic(), accumulator);
+            }
+        },
+        Some("--debug") => {
+            let stdin = io::stdin();
+            Debugger::new(program).repl(stdin.lock(), &mut io::stdout());
+        },
+        Some("--assemble") => print!("{}", write_canonical(&program)),
+        Some("--disassemble") => print!("{}", disassemble(&program)),
+        Some("--cfg") => print!("{}", control_flow_dot(&program)),
+        Some(other) => panic!("unknown option {}", other)
+    }
 }
 
 
@@ -28,114 +63,633 @@ fn open_file(file_path: &str) -> File {
 }
 
 
-fn count_lines(file: File) -> usize {
-    BufReader::new(file).lines().count()
+struct LoopReport {
+    // the accumulator just before an instruction runs a second time
+    acc: i32,
+    // the first instruction to be repeated
+    entry: usize,
+    // the instructions making up the loop, in execution order from the entry
+    cycle: Vec<usize>
 }
 
 
-fn reset_instructions(instructions: &mut Vec<Instruction>) {
-    for idx in 0..instructions.len() {
-        instructions[idx].visited = false
+fn find_loop(program: &[Instruction]) -> Option<LoopReport> {
+    let mut vm: Vm = Vm::new(program.to_vec());
+    match vm.run() {
+        Termination::InfiniteLoop { pc, acc } => {
+            let start: usize = vm.history.iter().position(|idx| *idx == pc).unwrap();
+            Some(LoopReport { acc, entry: pc, cycle: vm.history[start..].to_vec() })
+        },
+        _ => None
     }
 }
 
 
-fn debug_instructions(instructions: &mut Vec<Instruction>) -> i32 {
-    for idx in 0..instructions.len() {
-        reset_instructions(instructions);
-        let previous_instruction = instructions[idx].instruction;
-        match previous_instruction {
-            ACC => {continue;},
-            JMP => {instructions[idx].instruction = NOP;},
-            NOP => {instructions[idx].instruction = JMP;},
-            _ => panic!("oh no! invalid instruction")
+fn debug_instructions(program: &[Instruction]) -> Option<(usize, Opcode, i32)> {
+    find_repairs(program).into_iter().next()
+}
+
+
+// Rather than re-running the program once per candidate flip, first work
+// out which instructions lead to the end of the program as written (and
+// how much they add to the accumulator on the way). Then walk the original
+// execution once; flipping an instruction on that path fixes the program
+// exactly when the flipped instruction jumps somewhere that reaches the end.
+// That only holds if the original run never reaches the end: otherwise the
+// way to the end from the flip target may run back through the flipped
+// instruction, which now jumps somewhere else. A program that already
+// halts doesn't need repairing, so there are no repairs to find.
+fn find_repairs(program: &[Instruction]) -> Vec<(usize, Opcode, i32)> {
+    if let Termination::Halted(_) = Vm::new(program.to_vec()).run() {
+        return vec![];
+    }
+    let acc_to_end: Vec<Option<i32>> = accumulated_to_end(program);
+    let mut repairs: Vec<(usize, Opcode, i32)> = vec![];
+
+    let mut vm: Vm = Vm::new(program.to_vec());
+    while vm.termination().is_none() {
+        let idx: usize = vm.pc as usize;
+        if let Some(opcode) = program[idx].opcode.flipped() {
+            let next: i64 = successor(&Instruction { opcode, val: program[idx].val }, idx);
+            if next >= 0 && next <= program.len() as i64 {
+                if let Some(gain) = acc_to_end[next as usize] {
+                    repairs.push((idx, opcode, vm.acc + gain));
+                }
+            }
         }
-        let found_value: i32 = execute_instructions(instructions);
-        if found_value != -1 {
-            return found_value
+        vm.step();
+    }
+
+    repairs
+}
+
+
+fn successor(instruction: &Instruction, idx: usize) -> i64 {
+    let mut instruction_pointer: i64 = idx as i64;
+    execute_instruction(instruction, &mut instruction_pointer, &mut 0);
+    instruction_pointer
+}
+
+
+// For each instruction (plus the end of the program at index len), the
+// amount added to the accumulator between it and the end of the program,
+// or None if running from there never gets to the end.
+fn accumulated_to_end(program: &[Instruction]) -> Vec<Option<i32>> {
+    let mut predecessors: Vec<Vec<usize>> = vec![vec![]; program.len() + 1];
+    for (idx, instruction) in program.iter().enumerate() {
+        let next: i64 = successor(instruction, idx);
+        if next >= 0 && next <= program.len() as i64 {
+            predecessors[next as usize].push(idx);
         }
-        instructions[idx].instruction = previous_instruction;
     }
-    -1
+
+    // every instruction has exactly one successor, so each is reached once
+    let mut acc_to_end: Vec<Option<i32>> = vec![None; program.len() + 1];
+    acc_to_end[program.len()] = Some(0);
+    let mut queue: VecDeque<usize> = VecDeque::from(vec![program.len()]);
+    while let Some(idx) = queue.pop_front() {
+        for predecessor in &predecessors[idx] {
+            let mut gain: i32 = 0;
+            execute_instruction(&program[*predecessor], &mut 0, &mut gain);
+            acc_to_end[*predecessor] = acc_to_end[idx].map(|acc| acc + gain);
+            queue.push_back(*predecessor);
+        }
+    }
+
+    acc_to_end
 }
 
 
-fn execute_instructions(instructions: &mut Vec<Instruction>) -> i32 {
-    let mut accumulator: i32 = 0;
-    let mut instruction_pointer: i32 = 0;
-    while instruction_pointer >= 0 && (instruction_pointer as usize) < instructions.len() && !instructions[instruction_pointer as usize].visited {
-        execute_instruction(instructions, &mut instruction_pointer, &mut accumulator);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Opcode {
+    Acc,
+    Jmp,
+    Nop
+}
+
+
+impl Opcode {
+    fn parse(mnemonic: &str) -> Option<Opcode> {
+        match mnemonic {
+            "acc" => Some(Opcode::Acc),
+            "jmp" => Some(Opcode::Jmp),
+            "nop" => Some(Opcode::Nop),
+            _ => None
+        }
     }
-    if instruction_pointer as usize >= instructions.len() {
-        return accumulator
-    } else {
-        return -1
+
+    // the opcode a corrupted instruction might have been swapped from
+    fn flipped(&self) -> Option<Opcode> {
+        match self {
+            Opcode::Acc => None,
+            Opcode::Jmp => Some(Opcode::Nop),
+            Opcode::Nop => Some(Opcode::Jmp)
+        }
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Acc => "acc",
+            Opcode::Jmp => "jmp",
+            Opcode::Nop => "nop"
+        }
     }
 }
 
 
-fn execute_instruction(instructions: &mut Vec<Instruction>, instruction_pointer: &mut i32, accumulator: &mut i32) {
-    let current_instruction: &mut Instruction = &mut instructions[*instruction_pointer as usize];
-    current_instruction.visited = true;
-    match current_instruction.instruction {
-        ACC => {
-            *accumulator += current_instruction.val;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Instruction {
+    opcode: Opcode,
+    val: i32
+}
+
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:+}", self.opcode.mnemonic(), self.val)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Termination {
+    // the instruction pointer landed just past the last instruction
+    Halted(i32),
+    // the instruction at pc was about to run for a second time
+    InfiniteLoop { pc: usize, acc: i32 },
+    // the instruction pointer landed anywhere else outside the program
+    OutOfBounds(i64)
+}
+
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Termination::Halted(acc) => write!(f, "halted with acc {}", acc),
+            Termination::InfiniteLoop { pc, acc } => write!(f, "instruction {} would run again with acc {}", pc, acc),
+            Termination::OutOfBounds(pc) => write!(f, "jumped out of the program to {}", pc)
+        }
+    }
+}
+
+
+fn execute_instruction(instruction: &Instruction, instruction_pointer: &mut i64, accumulator: &mut i32) {
+    match instruction.opcode {
+        Opcode::Acc => {
+            *accumulator += instruction.val;
             *instruction_pointer += 1;
         },
-        JMP => {
-            *instruction_pointer += current_instruction.val;
+        Opcode::Jmp => {
+            *instruction_pointer += instruction.val as i64;
         },
-        NOP => {
+        Opcode::Nop => {
             *instruction_pointer += 1;
-        },
-        _ => panic!("invalid instruction!")
+        }
+    }
+}
+
+
+// The program itself is never modified by running it; which
+// instructions have been executed is tracked alongside it.
+struct Vm {
+    program: Vec<Instruction>,
+    visited: Vec<bool>,
+    // every instruction executed, in order
+    history: Vec<usize>,
+    pc: i64,
+    acc: i32
+}
+
+
+impl Vm {
+    fn new(program: Vec<Instruction>) -> Vm {
+        let visited: Vec<bool> = vec![false; program.len()];
+        Vm { program, visited, history: vec![], pc: 0, acc: 0 }
+    }
+
+    fn reset(&mut self) {
+        self.visited.iter_mut().for_each(|visited| *visited = false);
+        self.history.clear();
+        self.pc = 0;
+        self.acc = 0;
+    }
+
+    // why the machine can't execute the instruction at pc, if it can't
+    fn termination(&self) -> Option<Termination> {
+        if self.pc == self.program.len() as i64 {
+            return Some(Termination::Halted(self.acc));
+        }
+        if self.pc < 0 || self.pc > self.program.len() as i64 {
+            return Some(Termination::OutOfBounds(self.pc));
+        }
+        if self.visited[self.pc as usize] {
+            return Some(Termination::InfiniteLoop { pc: self.pc as usize, acc: self.acc });
+        }
+        None
+    }
+
+    // execute a single instruction, unless the machine has stopped
+    fn step(&mut self) -> Option<Termination> {
+        if let Some(termination) = self.termination() {
+            return Some(termination);
+        }
+        let idx: usize = self.pc as usize;
+        self.visited[idx] = true;
+        self.history.push(idx);
+        execute_instruction(&self.program[idx], &mut self.pc, &mut self.acc);
+        None
+    }
+
+    fn run(&mut self) -> Termination {
+        loop {
+            if let Some(termination) = self.step() {
+                return termination;
+            }
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Breakpoint {
+    Pc(usize),
+    Opcode(Opcode)
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Watchpoint {
+    // stop whenever an instruction changes the accumulator
+    Changed,
+    // stop when the accumulator becomes this value
+    Equals(i32)
+}
+
+
+// A line oriented debugger around the Vm. Commands are read one per line,
+// so a session can be typed interactively or piped in as a script:
+//
+//   step [n]                 execute n instructions (default 1), stopping
+//                            early at a breakpoint, watchpoint or termination
+//   continue                 run until a breakpoint, watchpoint or termination
+//   break <pc>|<opcode>      stop before instruction pc, or before any acc/jmp/nop
+//   watch [value]            stop when the accumulator changes (or equals value)
+//   clear                    remove every breakpoint and watchpoint
+//   print                    show pc, accumulator and the next instruction
+//   visited                  list the instructions that have run since the
+//                            start or the last patch
+//   patch <idx> <op> <val>   replace an instruction, e.g. "patch 7 nop -4", and
+//                            forget which instructions have run so the program
+//                            can carry on from where it stopped
+//   reset                    start the program again from the top
+//   quit
+struct Debugger {
+    vm: Vm,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>
+}
+
+
+impl Debugger {
+    fn new(program: Vec<Instruction>) -> Debugger {
+        Debugger { vm: Vm::new(program), breakpoints: vec![], watchpoints: vec![] }
+    }
+
+    fn repl(&mut self, input: impl BufRead, out: &mut dyn Write) {
+        for line in input.lines() {
+            let line: String = line.unwrap();
+            if !self.execute_command(line.trim(), out) {
+                break;
+            }
+        }
+    }
+
+    // returns false once the session should end
+    fn execute_command(&mut self, command: &str, out: &mut dyn Write) -> bool {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let result: Result<(), String> = match words.as_slice() {
+            [] => Ok(()),
+            ["quit"] | ["q"] => return false,
+            ["step"] | ["s"] => self.step(1, out),
+            ["step", n] | ["s", n] => match n.parse::<usize>() {
+                Ok(n) => self.step(n, out),
+                Err(_) => Err(format!("can't step {} times", n))
+            },
+            ["continue"] | ["c"] => self.cont(out),
+            ["break", target] | ["b", target] => self.add_breakpoint(target, out),
+            ["watch"] => {
+                self.watchpoints.push(Watchpoint::Changed);
+                writeln!(out, "watching the accumulator").unwrap();
+                Ok(())
+            },
+            ["watch", value] => match value.parse::<i32>() {
+                Ok(value) => {
+                    self.watchpoints.push(Watchpoint::Equals(value));
+                    writeln!(out, "watching for acc {}", value).unwrap();
+                    Ok(())
+                },
+                Err(_) => Err(format!("can't watch for {}", value))
+            },
+            ["clear"] => {
+                self.breakpoints.clear();
+                self.watchpoints.clear();
+                Ok(())
+            },
+            ["print"] | ["p"] => {
+                self.print_status(out);
+                Ok(())
+            },
+            ["visited"] => {
+                let visited: Vec<String> = (0..self.vm.visited.len())
+                    .filter(|idx| self.vm.visited[*idx])
+                    .map(|idx| idx.to_string())
+                    .collect();
+                writeln!(out, "visited: {}", visited.join(" ")).unwrap();
+                Ok(())
+            },
+            ["patch", idx, opcode, val] => self.patch(idx, opcode, val, out),
+            ["reset"] => {
+                self.vm.reset();
+                self.print_status(out);
+                Ok(())
+            },
+            _ => Err(format!("unknown command \"{}\"", command))
+        };
+        if let Err(message) = result {
+            writeln!(out, "error: {}", message).unwrap();
+        }
+        true
+    }
+
+    fn print_status(&self, out: &mut dyn Write) {
+        match self.vm.termination() {
+            Some(termination) => writeln!(out, "{}", termination),
+            None => writeln!(
+                out, "pc {} acc {}: {}",
+                self.vm.pc, self.vm.acc, self.vm.program[self.vm.pc as usize]
+            )
+        }.unwrap();
+    }
+
+    fn step(&mut self, n: usize, out: &mut dyn Write) -> Result<(), String> {
+        self.run_until(Some(n), out);
+        Ok(())
+    }
+
+    fn cont(&mut self, out: &mut dyn Write) -> Result<(), String> {
+        self.run_until(None, out);
+        Ok(())
+    }
+
+    // executes up to limit instructions (or without limit), stopping early
+    // at breakpoints, watchpoints and termination
+    fn run_until(&mut self, limit: Option<usize>, out: &mut dyn Write) {
+        let mut n_executed: usize = 0;
+        while limit.is_none_or(|limit| n_executed < limit) {
+            // always get off the instruction we're stopped at first
+            if n_executed > 0 {
+                if let Some(breakpoint) = self.breakpoint_hit() {
+                    writeln!(out, "breakpoint {:?}", breakpoint).unwrap();
+                    break;
+                }
+            }
+            n_executed += 1;
+
+            let acc: i32 = self.vm.acc;
+            if self.vm.step().is_some() {
+                break;
+            }
+            if let Some(watchpoint) = self.watchpoint_hit(acc) {
+                writeln!(out, "watchpoint {:?}: acc {} -> {}", watchpoint, acc, self.vm.acc).unwrap();
+                break;
+            }
+        }
+        self.print_status(out);
+    }
+
+    fn breakpoint_hit(&self) -> Option<Breakpoint> {
+        if self.vm.termination().is_some() {
+            return None;
+        }
+        let pc: usize = self.vm.pc as usize;
+        self.breakpoints.iter().find(|breakpoint| match breakpoint {
+            Breakpoint::Pc(idx) => *idx == pc,
+            Breakpoint::Opcode(opcode) => *opcode == self.vm.program[pc].opcode
+        }).copied()
+    }
+
+    fn watchpoint_hit(&self, previous_acc: i32) -> Option<Watchpoint> {
+        self.watchpoints.iter().find(|watchpoint| match watchpoint {
+            Watchpoint::Changed => previous_acc != self.vm.acc,
+            Watchpoint::Equals(value) => previous_acc != self.vm.acc && *value == self.vm.acc
+        }).copied()
     }
+
+    fn add_breakpoint(&mut self, target: &str, out: &mut dyn Write) -> Result<(), String> {
+        let breakpoint: Breakpoint = match (target.parse::<usize>(), Opcode::parse(target)) {
+            (Ok(idx), _) if idx < self.vm.program.len() => Breakpoint::Pc(idx),
+            (Ok(idx), _) => return Err(format!("there is no instruction {}", idx)),
+            (_, Some(opcode)) => Breakpoint::Opcode(opcode),
+            _ => return Err(format!("can't break on {}", target))
+        };
+        self.breakpoints.push(breakpoint);
+        writeln!(out, "breakpoint {:?}", breakpoint).unwrap();
+        Ok(())
+    }
+
+    fn patch(&mut self, idx: &str, opcode: &str, val: &str, out: &mut dyn Write) -> Result<(), String> {
+        let idx: usize = match idx.parse::<usize>() {
+            Ok(idx) if idx < self.vm.program.len() => idx,
+            _ => return Err(format!("there is no instruction {}", idx))
+        };
+        let opcode: Opcode = Opcode::parse(opcode).ok_or(format!("unknown opcode {}", opcode))?;
+        let val: i32 = val.parse::<i32>().map_err(|_| format!("bad value {}", val))?;
+        self.vm.program[idx] = Instruction { opcode, val };
+        // start loop detection afresh from here. Clearing just the patched
+        // instruction and the pc we're stopped at isn't enough: the rest of
+        // the old loop would still be marked, so carrying on would stop again
+        self.vm.visited.iter_mut().for_each(|visited| *visited = false);
+        writeln!(out, "{}: {}", idx, self.vm.program[idx]).unwrap();
+        Ok(())
+    }
+}
+
+
+#[derive(Debug, PartialEq, Eq)]
+struct AsmError {
+    line: usize,
+    message: String
 }
 
 
-fn parse_instructions(lines: Box<dyn Iterator<Item = String>>, n_lines: usize) -> Vec<Instruction> {
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+
+enum Operand {
+    Offset(i32),
+    Label(String)
+}
+
+
+// Assemble a program. The puzzle's own "jmp +4" format is accepted as is,
+// along with "# comments", blank lines, and labels, which can sit on their
+// own line or in front of an instruction:
+//
+//   loop: acc +1   # count
+//         jmp loop
+//
+// A label used as the operand of a jmp or nop becomes the offset from
+// that instruction to the label.
+fn parse_instructions(lines: Box<dyn Iterator<Item = String>>) -> Result<Vec<Instruction>, AsmError> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(
-            r"^([a-z]{3}) ([+-]{1}[0-9]*)$"
+        static ref LABEL_RE: Regex = Regex::new(
+            r"^([A-Za-z_][A-Za-z0-9_]*):\s*(.*)$"
+        ).unwrap();
+        static ref INSTRUCTION_RE: Regex = Regex::new(
+            r"^([a-z]+)\s+(?:([+-]?[0-9]+)|([A-Za-z_][A-Za-z0-9_]*))$"
         ).unwrap();
     }
-    let mut instructions: Vec<Instruction> = vec![Instruction::fresh(); n_lines];
-    
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut statements: Vec<(usize, Opcode, Operand)> = vec![];
+
     for (idx, line) in lines.enumerate() {
-        let (instruction, val): (&str, i32) = RE.captures(&line).and_then(
-            | capture | Some((
-                capture.get(1).unwrap().as_str(),
-                capture.get(2).unwrap().as_str().parse::<i32>().unwrap()
-            ))
-        ).unwrap();
-        instructions[idx].instruction = match instruction {
-            "acc" => ACC,
-            "jmp" => JMP,
-            "nop" => NOP,
-            _ => panic!("oh no!")
+        let line_no: usize = idx + 1;
+        let error = |message: String| AsmError { line: line_no, message };
+
+        let mut text: &str = line.split('#').next().unwrap().trim();
+        if let Some(capture) = LABEL_RE.captures(text) {
+            let label: &str = capture.get(1).unwrap().as_str();
+            if labels.insert(label.to_string(), statements.len()).is_some() {
+                return Err(error(format!("label {} is defined twice", label)));
+            }
+            text = capture.get(2).unwrap().as_str();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let capture = INSTRUCTION_RE.captures(text).ok_or_else(|| error(format!("can't parse \"{}\"", text)))?;
+        let mnemonic: &str = capture.get(1).unwrap().as_str();
+        let opcode: Opcode = Opcode::parse(mnemonic).ok_or_else(|| error(format!("unknown opcode {}", mnemonic)))?;
+        let operand: Operand = match (capture.get(2), capture.get(3)) {
+            (Some(val), _) => Operand::Offset(
+                val.as_str().parse::<i32>().map_err(|_| error(format!("{} is out of range", val.as_str())))?
+            ),
+            (_, Some(label)) if opcode != Opcode::Acc => Operand::Label(label.as_str().to_string()),
+            (_, Some(label)) => return Err(error(format!("acc needs a number, not {}", label.as_str()))),
+            _ => unreachable!()
         };
-        instructions[idx].val = val;
+        statements.push((line_no, opcode, operand));
     }
-    instructions
+
+    statements.iter().enumerate().map(|(idx, (line_no, opcode, operand))| {
+        let val: i32 = match operand {
+            Operand::Offset(val) => *val,
+            Operand::Label(label) => match labels.get(label) {
+                Some(target) => *target as i32 - idx as i32,
+                None => return Err(AsmError { line: *line_no, message: format!("unknown label {}", label) })
+            }
+        };
+        Ok(Instruction { opcode: *opcode, val })
+    }).collect()
 }
 
 
-#[derive(Clone)]
-struct Instruction {
-    val: i32,
-    instruction: &'static str,
-    visited: bool
+// the exact one-instruction-per-line format the puzzle uses
+fn write_canonical(program: &[Instruction]) -> String {
+    program.iter().map(|instruction| format!("{}\n", instruction)).collect()
+}
+
+
+fn jump_target(program: &[Instruction], idx: usize) -> Option<usize> {
+    match program[idx].opcode {
+        Opcode::Jmp => {
+            let target: i64 = successor(&program[idx], idx);
+            if target >= 0 && target <= program.len() as i64 {
+                Some(target as usize)
+            } else {
+                None
+            }
+        },
+        _ => None
+    }
 }
 
 
-impl Instruction {
-    fn fresh() -> Instruction {
-        Instruction {
-            val: 0,
-            instruction: UNINITIATED,
-            visited: false
+// The inverse of parse_instructions: every jmp that lands inside the
+// program (or exactly at its end) gets a label, named in program order.
+fn disassemble(program: &[Instruction]) -> String {
+    let mut targets: Vec<usize> = (0..program.len()).filter_map(|idx| jump_target(program, idx)).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let label = |target: usize| format!("L{}", targets.binary_search(&target).unwrap());
+
+    let mut text: String = String::new();
+    for idx in 0..=program.len() {
+        if targets.binary_search(&idx).is_ok() {
+            text.push_str(&format!("{}:\n", label(idx)));
+        }
+        if idx == program.len() {
+            break;
+        }
+        match jump_target(program, idx) {
+            Some(target) => text.push_str(&format!("    {} {}\n", program[idx].opcode.mnemonic(), label(target))),
+            None => text.push_str(&format!("    {}\n", program[idx]))
         }
     }
+    text
+}
+
+
+// Basic blocks start at instruction 0, at every jump target and right
+// after every jump. Each block ends in either a jump edge or a fall through
+// edge to the next block; the end of the program and jumps outside it get
+// their own nodes.
+fn control_flow_dot(program: &[Instruction]) -> String {
+    let mut leaders: Vec<usize> = vec![0];
+    for idx in 0..program.len() {
+        if program[idx].opcode == Opcode::Jmp {
+            leaders.push(idx + 1);
+            leaders.extend(jump_target(program, idx));
+        }
+    }
+    leaders.retain(|leader| *leader < program.len());
+    leaders.sort_unstable();
+    leaders.dedup();
+
+    let node = |idx: usize| if idx == program.len() { "end".to_string() } else { format!("b{}", idx) };
+    let mut dot: String = "digraph program {\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+    let mut edges: Vec<String> = vec![];
+    let mut out_of_bounds: bool = false;
+    for (block, start) in leaders.iter().enumerate() {
+        let end: usize = *leaders.get(block + 1).unwrap_or(&program.len());
+        let label: String = (*start..end).map(|idx| format!("{}: {}\\l", idx, program[idx])).collect();
+        dot.push_str(&format!("    {} [label=\"{}\"];\n", node(*start), label));
+
+        let last: usize = end - 1;
+        if program[last].opcode == Opcode::Jmp {
+            match jump_target(program, last) {
+                Some(target) => edges.push(format!("    {} -> {} [label=\"jmp\"];\n", node(*start), node(target))),
+                None => {
+                    out_of_bounds = true;
+                    edges.push(format!("    {} -> out_of_bounds [label=\"jmp\"];\n", node(*start)));
+                }
+            }
+        } else {
+            edges.push(format!("    {} -> {};\n", node(*start), node(end)));
+        }
+    }
+    dot.push_str("    end [shape=oval];\n");
+    if out_of_bounds {
+        dot.push_str("    out_of_bounds [shape=oval, label=\"out of bounds\"];\n");
+    }
+    edges.iter().for_each(|edge| dot.push_str(edge));
+    dot.push_str("}\n");
+    dot
 }
 
 
@@ -158,9 +712,200 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(lines: &[&str]) -> Result<Vec<Instruction>, AsmError> {
+        let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        parse_instructions(Box::new(lines.into_iter()))
+    }
+
+    fn program(lines: &[&str]) -> Vec<Instruction> {
+        assemble(lines).unwrap()
+    }
+
+    fn example_program() -> Vec<Instruction> {
+        program(&[
+            "nop +0", "acc +1", "jmp +4", "acc +3", "jmp -3",
+            "acc -99", "acc +1", "jmp -4", "acc +6"
+        ])
+    }
+
+    #[test]
+    fn test_parse_instructions() {
+        assert_eq!(
+            program(&["acc -7", "jmp +2"]),
+            vec![Instruction { opcode: Opcode::Acc, val: -7 }, Instruction { opcode: Opcode::Jmp, val: 2 }]
+        );
+        assert_eq!(Opcode::parse("nop"), Some(Opcode::Nop));
+        assert_eq!(Opcode::parse("hcf"), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut vm = Vm::new(example_program());
+        assert_eq!(vm.run(), Termination::InfiniteLoop { pc: 1, acc: 5 });
+
+        let mut vm = Vm::new(example_program());
+        assert_eq!(vm.step(), None);
+        assert_eq!((vm.pc, vm.acc), (1, 0));
+        assert_eq!(vm.step(), None);
+        assert_eq!((vm.pc, vm.acc), (2, 1));
+
+        assert_eq!(Vm::new(program(&["acc +2", "nop -5"])).run(), Termination::Halted(2));
+        assert_eq!(Vm::new(program(&["acc +2", "jmp -5"])).run(), Termination::OutOfBounds(-4));
+    }
+
+    #[test]
+    fn test_find_loop() {
+        let report = find_loop(&example_program()).unwrap();
+        assert_eq!(report.acc, 5);
+        assert_eq!(report.entry, 1);
+        assert_eq!(report.cycle, vec![1, 2, 6, 7, 3, 4]);
+
+        assert!(find_loop(&program(&["nop +0", "acc +1"])).is_none());
+    }
+
+    #[test]
+    fn test_debug_instructions() {
+        assert_eq!(debug_instructions(&example_program()), Some((7, Opcode::Nop, 8)));
+        assert_eq!(Opcode::Nop.mnemonic(), "nop");
+
+        // a real accumulator of -1 is no longer confused with "not found"
+        assert_eq!(debug_instructions(&program(&["acc -1", "jmp +0"])), Some((1, Opcode::Nop, -1)));
+        assert_eq!(debug_instructions(&program(&["jmp +0", "jmp -1"])), None);
+    }
+
+    #[test]
+    fn test_find_repairs() {
+        let repairs = find_repairs(&program(&["nop +3", "jmp +0", "acc +1", "acc +3"]));
+        assert_eq!(repairs, vec![(0, Opcode::Jmp, 3), (1, Opcode::Nop, 4)]);
+
+        // flipping the nop would make it jump to itself forever, and the
+        // program halts as it is anyway
+        assert_eq!(find_repairs(&program(&["nop +0", "acc +1"])), vec![]);
+    }
+
+    fn debug_session(program: Vec<Instruction>, script: &str) -> String {
+        let mut out: Vec<u8> = vec![];
+        Debugger::new(program).repl(script.as_bytes(), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_debugger_step_and_break() {
+        let transcript = debug_session(example_program(), "print\nstep 2\nbreak 4\ncontinue\nvisited\ncontinue\nquit\nstep\n");
+        assert_eq!(transcript, concat!(
+            "pc 0 acc 0: nop +0\n",
+            "pc 2 acc 1: jmp +4\n",
+            "breakpoint Pc(4)\n",
+            "breakpoint Pc(4)\n",
+            "pc 4 acc 5: jmp -3\n",
+            "visited: 0 1 2 3 6 7\n",
+            "instruction 1 would run again with acc 5\n"
+        ));
+    }
+
+    #[test]
+    fn test_debugger_watch_and_patch() {
+        let transcript = debug_session(example_program(), "break jmp\nwatch 4\nc\nclear\npatch 7 nop -4\nc\nbogus\n");
+        assert_eq!(transcript, concat!(
+            "breakpoint Opcode(Jmp)\n",
+            "watching for acc 4\n",
+            "breakpoint Opcode(Jmp)\n",
+            "pc 2 acc 1: jmp +4\n",
+            "7: nop -4\n",
+            "halted with acc 8\n",
+            "error: unknown command \"bogus\"\n"
+        ));
+
+        // run into the loop first, then patch it and carry on
+        let transcript = debug_session(example_program(), "c\npatch 7 nop -4\nc\nvisited\n");
+        assert_eq!(transcript, concat!(
+            "instruction 1 would run again with acc 5\n",
+            "7: nop -4\n",
+            "halted with acc 13\n",
+            "visited: 1 2 6 7 8\n"
+        ));
+    }
+
+    #[test]
+    fn test_debugger_step_stops_early() {
+        let transcript = debug_session(example_program(), "break 3\nstep 1000\nwatch\nstep 1000\nclear\nstep 1000\n");
+        assert_eq!(transcript, concat!(
+            "breakpoint Pc(3)\n",
+            "breakpoint Pc(3)\n",
+            "pc 3 acc 2: acc +3\n",
+            "watching the accumulator\n",
+            "watchpoint Changed: acc 2 -> 5\n",
+            "pc 4 acc 5: jmp -3\n",
+            "instruction 1 would run again with acc 5\n"
+        ));
+    }
+
+    #[test]
+    fn test_assemble_labels() {
+        let source = [
+            "# count to three, then fall off the end",
+            "start:  nop done",
+            "loop:",
+            "    acc +1   # one more",
+            "    jmp loop",
+            "done:"
+        ];
+        assert_eq!(write_canonical(&program(&source)), "nop +3\nacc +1\njmp -1\n");
+
+        assert_eq!(assemble(&["jmp nowhere"]).err().unwrap().message, "unknown label nowhere");
+        assert_eq!(assemble(&["a:", "a: nop +0"]).err().unwrap(), AsmError { line: 2, message: "label a is defined twice".to_string() });
+        assert_eq!(assemble(&["", "acc a"]).err().unwrap().line, 2);
+        assert_eq!(assemble(&["hcf +1"]).err().unwrap().message, "unknown opcode hcf");
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let disassembled = disassemble(&example_program());
+        assert_eq!(disassembled, concat!(
+            "    nop +0\n",
+            "L0:\n",
+            "    acc +1\n",
+            "    jmp L2\n",
+            "L1:\n",
+            "    acc +3\n",
+            "    jmp L0\n",
+            "    acc -99\n",
+            "L2:\n",
+            "    acc +1\n",
+            "    jmp L1\n",
+            "    acc +6\n"
+        ));
+        let lines: Vec<&str> = disassembled.lines().collect();
+        assert_eq!(program(&lines), example_program());
+    }
+
+    #[test]
+    fn test_control_flow_dot() {
+        let dot = control_flow_dot(&program(&["acc +1", "jmp +2", "acc +2", "nop +0", "jmp -9"]));
+        assert_eq!(dot, concat!(
+            "digraph program {\n",
+            "    node [shape=box, fontname=\"monospace\"];\n",
+            "    b0 [label=\"0: acc +1\\l1: jmp +2\\l\"];\n",
+            "    b2 [label=\"2: acc +2\\l\"];\n",
+            "    b3 [label=\"3: nop +0\\l4: jmp -9\\l\"];\n",
+            "    end [shape=oval];\n",
+            "    out_of_bounds [shape=oval, label=\"out of bounds\"];\n",
+            "    b0 -> b3 [label=\"jmp\"];\n",
+            "    b2 -> b3;\n",
+            "    b3 -> out_of_bounds [label=\"jmp\"];\n",
+            "}\n"
+        ));
+    }
+}
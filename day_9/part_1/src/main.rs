@@ -1,83 +1,232 @@
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Lines};
+use std::ops::Range;
 
-const PREAMBLE_SIZE: usize = 25;
+const DEFAULT_PREAMBLE_SIZE: usize = 25;
 
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+    let mut preamble_size: usize = DEFAULT_PREAMBLE_SIZE;
+    let mut find_all: bool = false;
+    for arg in &args[2..] {
+        match arg.as_str() {
+            "--all" => find_all = true,
+            // no pair of values can be formed from a smaller preamble
+            size => preamble_size = size.parse::<usize>().ok()
+                .filter(|size| *size >= 2)
+                .expect("the preamble size must be a number of at least 2")
+        }
+    }
+
+    if find_all {
+        let vals: Vec<i64> = parse_values(Box::new(AocBufReader::from_file(open_file(filename)))).collect();
+        for (idx, imposter) in InvalidNumbers::new(vals.iter().copied(), preamble_size) {
+            println!("Value {} ({}) is an imposter", idx, imposter);
+            for range in find_all_contiguous_ranges(&vals, imposter) {
+                println!(
+                    "    values {}..{} add up to it, special sum {}",
+                    range.start, range.end, encryption_weakness(&vals[range.clone()])
+                );
+            }
+        }
+        return;
+    }
+
     let aoc_reader = AocBufReader::from_file(open_file(filename));
-    let imposter: i64 = find_imposter(Box::new(aoc_reader));
+    let imposter: i64 = match find_imposter(Box::new(aoc_reader), preamble_size) {
+        Some(imposter) => imposter,
+        None => {
+            println!("All numbers are valid!");
+            return;
+        }
+    };
     println!("The imposter is {}", imposter);
 
     let aoc_reader = AocBufReader::from_file(open_file(filename));
-    let special_sum: i64 = find_contiguous_block(Box::new(aoc_reader), &imposter);
-    println!("The special sum is {}", special_sum);
-
+    match find_contiguous_block(Box::new(aoc_reader), imposter) {
+        Some(special_sum) => println!("The special sum is {}", special_sum),
+        None => println!("No contiguous block adds up to the imposter!")
+    }
 }
 
 fn open_file(file_path: &str) -> File {
     File::open(file_path).unwrap()
 }
 
-fn find_contiguous_block(lines: Box<dyn Iterator<Item = String>>, imposter: &i64) -> i64 {
-    let mut vals: Vec<i64> = vec![];
-    for line in lines {
-        vals.push(line.parse::<i64>().unwrap());
+fn find_contiguous_block(lines: Box<dyn Iterator<Item = String>>, imposter: i64) -> Option<i64> {
+    let vals: Vec<i64> = parse_values(lines).collect();
+    let range: Range<usize> = find_contiguous_range(&vals, imposter)?;
+    Some(encryption_weakness(&vals[range]))
+}
+
+
+fn encryption_weakness(block: &[i64]) -> i64 {
+    block.iter().min().unwrap() + block.iter().max().unwrap()
+}
+
+
+// The block of at least two consecutive values adding up to target that
+// ends earliest (and, of those, starts earliest). Non-negative inputs use
+// a sliding window; anything with negative values falls back to prefix sums.
+fn find_contiguous_range(vals: &[i64], target: i64) -> Option<Range<usize>> {
+    if vals.iter().all(|val| *val >= 0) {
+        find_range_sliding_window(vals, target)
+    } else {
+        find_ranges_prefix_sums(vals, target, false).into_iter().next()
     }
+}
+
+
+// every block of at least two consecutive values adding up to target,
+// ordered by where they end and then where they start
+fn find_all_contiguous_ranges(vals: &[i64], target: i64) -> Vec<Range<usize>> {
+    find_ranges_prefix_sums(vals, target, true)
+}
 
-    let (start, end) = find_slice_bounds(&vals, imposter);
 
-    *vals[start..end].iter().min().unwrap()
-    + *vals[start..end].iter().max().unwrap()
+fn find_range_sliding_window(vals: &[i64], target: i64) -> Option<Range<usize>> {
+    // with no negative values, growing the window never shrinks the sum,
+    // so the start only ever has to move forward
+    let mut start: usize = 0;
+    let mut sum: i64 = 0;
+    for (end, val) in vals.iter().enumerate() {
+        sum += val;
+        while sum > target && start <= end {
+            sum -= vals[start];
+            start += 1;
+        }
+        if sum == target && end > start {
+            return Some(start..end + 1);
+        }
+    }
+    None
 }
 
-fn find_slice_bounds(vals: &Vec<i64>, imposter: &i64) -> (usize, usize) {
-    for idx_1 in 0..(vals.len() - 1) {
-        let mut sum = vals[idx_1];
-        for idx_2 in (idx_1 + 1)..vals.len() {
-            sum += vals[idx_2];
-            if sum > *imposter {
-                break;
-            } else if sum == *imposter {
-                return (idx_1, idx_2 + 1)
+
+fn find_ranges_prefix_sums(vals: &[i64], target: i64, find_all: bool) -> Vec<Range<usize>> {
+    // vals[start..end] sums to prefix[end] - prefix[start]
+    let mut prefix: Vec<i64> = vec![0];
+    for val in vals {
+        prefix.push(prefix[prefix.len() - 1] + val);
+    }
+
+    let mut starts: HashMap<i64, Vec<usize>> = HashMap::new();
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for end in 2..prefix.len() {
+        // only starts at least two values back are allowed
+        starts.entry(prefix[end - 2]).or_default().push(end - 2);
+        if let Some(candidates) = starts.get(&(prefix[end] - target)) {
+            for start in candidates {
+                ranges.push(*start..end);
+                if !find_all {
+                    return ranges;
+                }
             }
         }
     }
-    panic!("We didn't find a block!");
+    ranges
+}
+
+
+fn parse_values(lines: Box<dyn Iterator<Item = String>>) -> impl Iterator<Item = i64> {
+    lines.map(|line| line.parse::<i64>().unwrap())
 }
 
-fn find_imposter(mut lines: Box<dyn Iterator<Item = String>>) -> i64 {
-    let mut buffer: [i64;PREAMBLE_SIZE] = [-1; PREAMBLE_SIZE];
-    for idx in 0..PREAMBLE_SIZE{
-        buffer[idx as usize] = lines.next().unwrap().parse::<i64>().unwrap()
+
+fn find_imposter(lines: Box<dyn Iterator<Item = String>>, preamble_size: usize) -> Option<i64> {
+    InvalidNumbers::new(parse_values(lines), preamble_size)
+        .next()
+        .map(|(_idx, imposter)| imposter)
+}
+
+
+// The last `size` values, along with how many pairs of them add up to
+// each possible sum. Pushing a value costs O(size) to update the pair
+// sums, after which checking a candidate is a single lookup.
+struct XmasWindow {
+    size: usize,
+    values: VecDeque<i64>,
+    pair_sums: HashMap<i64, usize>
+}
+
+
+impl XmasWindow {
+    fn new(size: usize) -> XmasWindow {
+        // an empty window has nothing to evict, and a single value has no
+        // pairs, so neither could ever find a match
+        assert!(size >= 2, "the preamble size must be at least 2, not {}", size);
+        XmasWindow {
+            size,
+            values: VecDeque::with_capacity(size),
+            pair_sums: HashMap::new()
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.values.len() == self.size
+    }
+
+    fn push(&mut self, value: i64) {
+        if self.is_full() {
+            let oldest: i64 = self.values.pop_front().unwrap();
+            for other in &self.values {
+                let count = self.pair_sums.get_mut(&(oldest + other)).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.pair_sums.remove(&(oldest + other));
+                }
+            }
+        }
+        for other in &self.values {
+            *self.pair_sums.entry(value + other).or_insert(0) += 1;
+        }
+        self.values.push_back(value);
     }
-    
-    // the buffer is acting as a hacky ring cache!
-    let mut candidate: i64 = lines.next().unwrap().parse::<i64>().unwrap();
-    let mut first_out_ptr: usize = 0;
-    while is_match_found(&mut buffer, &candidate) {
-        buffer[first_out_ptr] = candidate;
-        first_out_ptr = (first_out_ptr + 1) % PREAMBLE_SIZE;
-        candidate = lines.next().unwrap().parse::<i64>().unwrap()
+
+    fn is_match_found(&self, candidate: i64) -> bool {
+        self.pair_sums.contains_key(&candidate)
     }
+}
 
-    candidate
+
+// Yields (index, value) for every value after the preamble that isn't
+// the sum of two of the `preamble_size` values before it.
+struct InvalidNumbers<I: Iterator<Item = i64>> {
+    values: I,
+    window: XmasWindow,
+    idx: usize
 }
 
-fn is_match_found(buffer: &mut [i64], candidate: &i64) -> bool {
-    for idx_1 in 0..(PREAMBLE_SIZE - 1) {
-        for idx_2 in (idx_1 + 1)..PREAMBLE_SIZE {
-            if buffer[idx_1] + buffer[idx_2] == *candidate {
-                return true
+
+impl<I: Iterator<Item = i64>> InvalidNumbers<I> {
+    fn new(values: I, preamble_size: usize) -> InvalidNumbers<I> {
+        InvalidNumbers { values, window: XmasWindow::new(preamble_size), idx: 0 }
+    }
+}
+
+
+impl<I: Iterator<Item = i64>> Iterator for InvalidNumbers<I> {
+    type Item = (usize, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for candidate in self.values.by_ref() {
+            let idx: usize = self.idx;
+            self.idx += 1;
+            let is_valid: bool = !self.window.is_full() || self.window.is_match_found(candidate);
+            self.window.push(candidate);
+            if !is_valid {
+                return Some((idx, candidate));
             }
-        } 
+        }
+        None
     }
-    false
 }
 
+
 struct AocBufReader {
     iter: Lines<BufReader<File>>,
 }
@@ -97,9 +246,75 @@ impl Iterator for AocBufReader {
         match self.iter.next() {
             Some(result) => match result {
                 Ok(line) => Some(line),
-                Err(error) => panic!(error)
+                Err(error) => panic!("{}", error)
             },
             None => None
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [i64; 20] = [
+        35, 20, 15, 25, 47, 40, 62, 55, 65, 95,
+        102, 117, 150, 182, 127, 219, 299, 277, 309, 576
+    ];
+
+    #[test]
+    fn test_invalid_numbers() {
+        let invalid: Vec<(usize, i64)> = InvalidNumbers::new(EXAMPLE.iter().copied(), 5).collect();
+        assert_eq!(invalid, vec![(14, 127)]);
+
+        let lines: Vec<String> = EXAMPLE.iter().map(|val| val.to_string()).collect();
+        assert_eq!(find_imposter(Box::new(lines.into_iter()), 5), Some(127));
+
+        // a longer preamble makes every number in the example valid
+        assert_eq!(InvalidNumbers::new(EXAMPLE.iter().copied(), 19).next(), None);
+        assert_eq!(InvalidNumbers::new(EXAMPLE.iter().copied(), 2).count(), 18);
+    }
+
+    #[test]
+    fn test_window_evicts_pair_sums() {
+        let mut window = XmasWindow::new(2);
+        window.push(1);
+        window.push(2);
+        assert!(window.is_match_found(3));
+        window.push(4);
+        assert!(!window.is_match_found(3));
+        assert!(window.is_match_found(6));
+        assert_eq!(window.pair_sums.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the preamble size must be at least 2, not 0")]
+    fn test_empty_preamble() {
+        InvalidNumbers::new(EXAMPLE.iter().copied(), 0).count();
+    }
+
+    #[test]
+    fn test_contiguous_range() {
+        assert_eq!(find_contiguous_range(&EXAMPLE, 127), Some(2..6));
+        assert_eq!(encryption_weakness(&EXAMPLE[2..6]), 62);
+
+        // a single value equal to the target doesn't count
+        assert_eq!(find_contiguous_range(&[1, 5, 1], 5), None);
+        assert_eq!(find_contiguous_range(&[1, 5, 0], 5), Some(1..3));
+        assert_eq!(find_contiguous_range(&[], 5), None);
+    }
+
+    #[test]
+    fn test_contiguous_range_with_negatives() {
+        assert_eq!(find_contiguous_range(&[9, -4, 1, 4, 3], 5), Some(0..2));
+        assert_eq!(find_contiguous_range(&[3, -8, 10], 2), Some(1..3));
+        assert_eq!(find_contiguous_range(&[-1, -1, 3], 5), None);
+    }
+
+    #[test]
+    fn test_all_contiguous_ranges() {
+        assert_eq!(find_all_contiguous_ranges(&[2, 3, 0, 5, -5, 5], 5), vec![0..2, 0..3, 2..4, 0..5, 2..6, 3..6]);
+        assert_eq!(find_all_contiguous_ranges(&EXAMPLE, 127), vec![2..6]);
+    }
+}